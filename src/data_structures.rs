@@ -1,4 +1,5 @@
 pub mod fifo;
+pub mod hamt;
+pub mod heap;
 pub mod lifo;
 pub mod lists;
-pub mod heap;
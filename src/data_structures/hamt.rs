@@ -0,0 +1,585 @@
+//! Persistent hash array mapped tries.
+//!
+//! Keys are placed by their 64-bit hash, five bits per level (Bagwell, 2001).
+//! Every branch keeps a 32-bit bitmap of the slots in use and a dense vector
+//! with one entry per set bit, so a lookup touches at most 13 levels and
+//! usually far fewer. Updates copy only the branches on the path to the key
+//! and share everything else with the previous version. Keys whose full
+//! hashes collide are kept together in a single collision entry.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::slice;
+use std::sync::Arc;
+
+const BITS: u32 = 5;
+
+/// Kind of reference-counted pointer that versions share nodes through.
+pub trait PointerFamily {
+    type Pointer<T>: Deref<Target = T> + Clone;
+
+    fn new<T>(value: T) -> Self::Pointer<T>;
+}
+
+/// Shares nodes through `Rc`, for use within a single thread.
+pub struct RcFamily;
+
+/// Shares nodes through `Arc`, so versions can be sent between threads.
+pub struct ArcFamily;
+
+impl PointerFamily for RcFamily {
+    type Pointer<T> = Rc<T>;
+
+    fn new<T>(value: T) -> Rc<T> {
+        Rc::new(value)
+    }
+}
+
+impl PointerFamily for ArcFamily {
+    type Pointer<T> = Arc<T>;
+
+    fn new<T>(value: T) -> Arc<T> {
+        Arc::new(value)
+    }
+}
+
+type Pair<K, V, P> = <P as PointerFamily>::Pointer<(K, V)>;
+
+enum Entry<K, V, P: PointerFamily> {
+    Leaf(u64, Pair<K, V, P>),
+    /// Two or more keys sharing the same full hash.
+    Collision(u64, Vec<Pair<K, V, P>>),
+    Branch(P::Pointer<Branch<K, V, P>>),
+}
+
+impl<K, V, P: PointerFamily> Clone for Entry<K, V, P> {
+    fn clone(&self) -> Self {
+        match self {
+            Entry::Leaf(hash, pair) => Entry::Leaf(*hash, pair.clone()),
+            Entry::Collision(hash, pairs) => Entry::Collision(*hash, pairs.clone()),
+            Entry::Branch(branch) => Entry::Branch(branch.clone()),
+        }
+    }
+}
+
+impl<K, V, P: PointerFamily> Entry<K, V, P> {
+    /// Hash of the keys below a leaf or collision entry.
+    fn hash(&self) -> u64 {
+        match self {
+            Entry::Leaf(hash, _) | Entry::Collision(hash, _) => *hash,
+            Entry::Branch(_) => unreachable!("branches hold keys with different hashes"),
+        }
+    }
+}
+
+struct Branch<K, V, P: PointerFamily> {
+    bitmap: u32,
+    entries: Vec<Entry<K, V, P>>,
+}
+
+impl<K, V, P: PointerFamily> Clone for Branch<K, V, P> {
+    fn clone(&self) -> Self {
+        Branch {
+            bitmap: self.bitmap,
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+/// Slot of `hash` in a branch `shift` bits down.
+fn slot(hash: u64, shift: u32) -> u32 {
+    1 << ((hash >> shift) & 31)
+}
+
+impl<K, V, P: PointerFamily> Branch<K, V, P> {
+    fn empty() -> Self {
+        Branch {
+            bitmap: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Position in `entries` of the entry for `bit`.
+    fn position(&self, bit: u32) -> usize {
+        (self.bitmap & (bit - 1)).count_ones() as usize
+    }
+
+    /// Branch holding two entries whose hashes differ, `shift` bits down.
+    fn pair(shift: u32, a: Entry<K, V, P>, b: Entry<K, V, P>) -> Self {
+        let (bit_a, bit_b) = (slot(a.hash(), shift), slot(b.hash(), shift));
+        if bit_a == bit_b {
+            let inner = Self::pair(shift + BITS, a, b);
+            return Branch {
+                bitmap: bit_a,
+                entries: vec![Entry::Branch(P::new(inner))],
+            };
+        }
+        let entries = if bit_a < bit_b {
+            vec![a, b]
+        } else {
+            vec![b, a]
+        };
+        Branch {
+            bitmap: bit_a | bit_b,
+            entries,
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, P: PointerFamily> Branch<K, V, P> {
+    fn get<Q>(&self, shift: u32, hash: u64, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bit = slot(hash, shift);
+        if self.bitmap & bit == 0 {
+            return None;
+        }
+        match &self.entries[self.position(bit)] {
+            Entry::Leaf(h, pair) if *h == hash && pair.0.borrow() == key => Some(&pair.1),
+            Entry::Collision(h, pairs) if *h == hash => pairs
+                .iter()
+                .find(|pair| pair.0.borrow() == key)
+                .map(|pair| &pair.1),
+            Entry::Branch(branch) => branch.get(shift + BITS, hash, key),
+            _ => None,
+        }
+    }
+
+    /// Copy of the branch with `pair` inserted, and whether its key is new.
+    fn insert(&self, shift: u32, hash: u64, pair: Pair<K, V, P>) -> (Self, bool) {
+        let bit = slot(hash, shift);
+        let position = self.position(bit);
+        let mut branch = self.clone();
+        if self.bitmap & bit == 0 {
+            branch.bitmap |= bit;
+            branch.entries.insert(position, Entry::Leaf(hash, pair));
+            return (branch, true);
+        }
+        let (entry, added) = match &self.entries[position] {
+            Entry::Leaf(h, existing) if *h == hash => {
+                if existing.0 == pair.0 {
+                    (Entry::Leaf(hash, pair), false)
+                } else {
+                    (Entry::Collision(hash, vec![existing.clone(), pair]), true)
+                }
+            }
+            Entry::Collision(h, pairs) if *h == hash => {
+                let mut pairs = pairs.clone();
+                let added = match pairs.iter().position(|existing| existing.0 == pair.0) {
+                    Some(i) => {
+                        pairs[i] = pair;
+                        false
+                    }
+                    None => {
+                        pairs.push(pair);
+                        true
+                    }
+                };
+                (Entry::Collision(hash, pairs), added)
+            }
+            Entry::Branch(child) => {
+                let (child, added) = child.insert(shift + BITS, hash, pair);
+                (Entry::Branch(P::new(child)), added)
+            }
+            other => {
+                let inner = Self::pair(shift + BITS, other.clone(), Entry::Leaf(hash, pair));
+                (Entry::Branch(P::new(inner)), true)
+            }
+        };
+        branch.entries[position] = entry;
+        (branch, added)
+    }
+
+    /// Copy of the branch without `key`, or `None` if it is not present.
+    fn remove<Q>(&self, shift: u32, hash: u64, key: &Q) -> Option<Self>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bit = slot(hash, shift);
+        if self.bitmap & bit == 0 {
+            return None;
+        }
+        let position = self.position(bit);
+        let replacement = match &self.entries[position] {
+            Entry::Leaf(h, pair) if *h == hash && pair.0.borrow() == key => None,
+            Entry::Collision(h, pairs) if *h == hash => {
+                let i = pairs.iter().position(|pair| pair.0.borrow() == key)?;
+                let mut pairs = pairs.clone();
+                pairs.remove(i);
+                if pairs.len() == 1 {
+                    Some(Entry::Leaf(hash, pairs.pop().unwrap()))
+                } else {
+                    Some(Entry::Collision(hash, pairs))
+                }
+            }
+            Entry::Branch(child) => {
+                let mut child = child.remove(shift + BITS, hash, key)?;
+                // Keep a lone leaf or collision at the highest level it fits.
+                match child.entries.len() {
+                    0 => None,
+                    1 if !matches!(child.entries[0], Entry::Branch(_)) => child.entries.pop(),
+                    _ => Some(Entry::Branch(P::new(child))),
+                }
+            }
+            _ => return None,
+        };
+        let mut branch = self.clone();
+        match replacement {
+            Some(entry) => branch.entries[position] = entry,
+            None => {
+                branch.bitmap &= !bit;
+                branch.entries.remove(position);
+            }
+        }
+        Some(branch)
+    }
+}
+
+fn hash_of<Q: Hash + ?Sized>(key: &Q) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Immutable hash map whose updates return new versions sharing structure
+/// with the old one, so cloning and keeping old versions around is cheap.
+///
+/// Nodes are shared through `Rc` by default; use `ArcFamily` for maps that
+/// cross threads.
+pub struct PersistentMap<K, V, P: PointerFamily = RcFamily> {
+    root: P::Pointer<Branch<K, V, P>>,
+    len: usize,
+}
+
+impl<K, V, P: PointerFamily> Clone for PersistentMap<K, V, P> {
+    fn clone(&self) -> Self {
+        PersistentMap {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<K, V, P: PointerFamily> PersistentMap<K, V, P> {
+    pub fn new() -> Self {
+        PersistentMap {
+            root: P::new(Branch::empty()),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Entries in no particular order.
+    pub fn iter(&self) -> Iter<'_, K, V, P> {
+        Iter {
+            stack: vec![self.root.entries.iter()],
+            collision: [].iter(),
+            remaining: self.len,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K: Hash + Eq, V, P: PointerFamily> PersistentMap<K, V, P> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.root.get(0, hash_of(key), key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// New version with `key` mapped to `value`, replacing any previous value.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let hash = hash_of(&key);
+        let (root, added) = self.root.insert(0, hash, P::new((key, value)));
+        PersistentMap {
+            root: P::new(root),
+            len: self.len + usize::from(added),
+        }
+    }
+
+    /// New version without `key`. Shares the whole map if it is not present.
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.root.remove(0, hash_of(key), key) {
+            Some(root) => PersistentMap {
+                root: P::new(root),
+                len: self.len - 1,
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+impl<K, V, P: PointerFamily> Default for PersistentMap<K, V, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq, P: PointerFamily> PartialEq for PersistentMap<K, V, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, P: PointerFamily> fmt::Debug for PersistentMap<K, V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V, P: PointerFamily> FromIterator<(K, V)> for PersistentMap<K, V, P> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |map, (k, v)| map.insert(k, v))
+    }
+}
+
+impl<'a, K, V, P: PointerFamily> IntoIterator for &'a PersistentMap<K, V, P> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a, K, V, P: PointerFamily> {
+    stack: Vec<slice::Iter<'a, Entry<K, V, P>>>,
+    collision: slice::Iter<'a, Pair<K, V, P>>,
+    remaining: usize,
+}
+
+impl<'a, K, V, P: PointerFamily> Iterator for Iter<'a, K, V, P> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pair) = self.collision.next() {
+                self.remaining -= 1;
+                return Some((&pair.0, &pair.1));
+            }
+            match self.stack.last_mut()?.next() {
+                None => {
+                    self.stack.pop();
+                }
+                Some(Entry::Leaf(_, pair)) => {
+                    self.remaining -= 1;
+                    return Some((&pair.0, &pair.1));
+                }
+                Some(Entry::Collision(_, pairs)) => self.collision = pairs.iter(),
+                Some(Entry::Branch(branch)) => self.stack.push(branch.entries.iter()),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, P: PointerFamily> ExactSizeIterator for Iter<'_, K, V, P> {}
+
+/// Immutable hash set backed by a `PersistentMap`.
+pub struct PersistentSet<T, P: PointerFamily = RcFamily> {
+    map: PersistentMap<T, (), P>,
+}
+
+impl<T, P: PointerFamily> Clone for PersistentSet<T, P> {
+    fn clone(&self) -> Self {
+        PersistentSet {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T, P: PointerFamily> PersistentSet<T, P> {
+    pub fn new() -> Self {
+        PersistentSet {
+            map: PersistentMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.map.keys()
+    }
+}
+
+impl<T: Hash + Eq, P: PointerFamily> PersistentSet<T, P> {
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(value)
+    }
+
+    pub fn insert(&self, value: T) -> Self {
+        PersistentSet {
+            map: self.map.insert(value, ()),
+        }
+    }
+
+    pub fn remove<Q>(&self, value: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        PersistentSet {
+            map: self.map.remove(value),
+        }
+    }
+}
+
+impl<T, P: PointerFamily> Default for PersistentSet<T, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash + Eq, P: PointerFamily> PartialEq for PersistentSet<T, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T: fmt::Debug, P: PointerFamily> fmt::Debug for PersistentSet<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Hash + Eq, P: PointerFamily> FromIterator<T> for PersistentSet<T, P> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        PersistentSet {
+            map: iter.into_iter().map(|value| (value, ())).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+    use std::thread;
+
+    /// Key whose hash only depends on `value % 4`, to force collisions.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Colliding(u32);
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            (self.0 % 4).hash(state);
+        }
+    }
+
+    #[test]
+    fn versions_match_hash_map() {
+        let mut rng = StdRng::seed_from_u64(50);
+        let mut versions: Vec<(PersistentMap<u32, u32>, HashMap<u32, u32>)> =
+            vec![(PersistentMap::new(), HashMap::new())];
+        for _ in 0..3000 {
+            let (map, model) = versions[rng.gen_range(0..versions.len())].clone();
+            let key = rng.gen_range(0..500);
+            let (map, model) = if rng.gen_bool(0.7) {
+                let value = rng.gen();
+                let mut model = model;
+                model.insert(key, value);
+                (map.insert(key, value), model)
+            } else {
+                let mut model = model;
+                model.remove(&key);
+                (map.remove(&key), model)
+            };
+            versions.push((map, model));
+        }
+        for (map, model) in versions.iter() {
+            assert_eq!(map.len(), model.len());
+            assert_eq!(map.iter().len(), model.len());
+            for (k, v) in map.iter() {
+                assert_eq!(model.get(k), Some(v));
+            }
+        }
+        let (map, model) = versions.last().unwrap();
+        assert_eq!(*map, model.iter().map(|(k, v)| (*k, *v)).collect());
+    }
+
+    #[test]
+    fn full_hash_collisions() {
+        let map: PersistentMap<Colliding, u32> = (0..40).map(|i| (Colliding(i), i)).collect();
+        assert_eq!(map.len(), 40);
+        assert!((0..40).all(|i| map.get(&Colliding(i)) == Some(&i)));
+        assert_eq!(map.get(&Colliding(40)), None);
+
+        let map = (0..40)
+            .filter(|i| i % 3 != 0)
+            .fold(map, |map, i| map.remove(&Colliding(i)));
+        assert_eq!(map.len(), 14);
+        assert!((0..40).all(|i| map.contains_key(&Colliding(i)) == (i % 3 == 0)));
+        let map = map.insert(Colliding(3), 300);
+        assert_eq!(map.get(&Colliding(3)), Some(&300));
+        assert_eq!(map.len(), 14);
+    }
+
+    #[test]
+    fn sets_and_arc_versions() {
+        let base: PersistentSet<String, ArcFamily> =
+            ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let handle = {
+            let base = base.clone();
+            thread::spawn(move || base.insert("d".to_string()))
+        };
+        let extended = handle.join().unwrap();
+        assert_eq!(base.len(), 3);
+        assert_eq!(extended.len(), 4);
+        assert!(extended.contains("d") && !base.contains("d"));
+        assert_eq!(extended.remove("d"), base);
+        assert_eq!(base.remove("z"), base);
+        assert!(PersistentSet::<u8>::new().is_empty());
+    }
+}
@@ -1,57 +1,112 @@
+//! Heap data structures.
+
+pub mod binomial;
+pub mod leftist;
+pub mod min_max;
+pub mod pairing;
+pub mod radix;
+pub mod select;
+
+use snafu::Snafu;
 use std::ops::Index;
-use std::rc::Rc;
 use std::vec::Vec;
 
-trait Heap<T> {
+pub use binomial::BinomialHeap;
+pub use leftist::LeftistHeap;
+pub use min_max::MinMaxHeap;
+pub use pairing::PairingHeap;
+pub use radix::RadixHeap;
+pub use select::{bottom_k, kmerge, top_k};
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum HeapError {
+    #[snafu(display("Handle does not refer to an element still in the heap"))]
+    InvalidHandle,
+    #[snafu(display(
+        "Key can only be decreased, but the new key is greater than the current one"
+    ))]
+    KeyIncrease,
+    #[snafu(display("Key is smaller than the last popped key of a monotone heap"))]
+    NonMonotone,
+}
+
+/// Common priority queue operations, with the smallest item at the top.
+pub trait Heap<T> {
     fn new() -> Self;
-    fn build_heap(&mut self) -> Self;
-    fn heapify(&mut self, i: usize) -> Self;
-    fn heapsort(items: Vec<T>) -> Self;
     fn push(&mut self, item: T);
-    fn pop(&mut self) -> T;
+    fn pop(&mut self) -> Option<T>;
     fn size(&self) -> usize;
-    fn parent(i: usize) -> usize { i / 2 }
-    fn left(i: usize) -> usize { 2 * i }
-    fn right(i: usize) -> usize { (2 * i) + 1 }
+    fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
 }
 
-struct HeapNode<T> {
-    pub key: usize,
-    pub val: T,
-    pub parent: Option<Rc<HeapNode<T>>>,
-    pub left: Option<Rc<HeapNode<T>>>,
-    pub right: Option<Rc<HeapNode<T>>>,
+/// Heaps that can be combined and whose items can be reprioritised in place.
+pub trait MergeableHeap<T>: Heap<T> {
+    type Handle;
+
+    /// Combines two heaps into one, consuming both.
+    fn meld(self, other: Self) -> Self;
+    /// Pushes an item, returning a handle usable with `decrease_key`.
+    fn push_with_handle(&mut self, item: T) -> Self::Handle;
+    /// Replaces the item behind `handle` with a smaller (or equal) one.
+    fn decrease_key(&mut self, handle: &Self::Handle, item: T) -> Result<(), HeapError>;
 }
 
-struct MinHeap<T> {
+/// Binary min-heap stored in a `Vec`.
+pub struct MinHeap<T> {
     pub size: usize,
     pub items: Vec<T>,
     property: fn(&T, &T) -> bool,
 }
 
-impl<T: Default + Ord> Heap<T> for MinHeap<T> {
-    fn new() -> Self {
-        MinHeap {
-            size: 0,
-            items: Vec::<T>::new(),
+impl<T: Ord> MinHeap<T> {
+    /// Builds a heap out of an unordered `Vec` in O(n).
+    pub fn from_vec(items: Vec<T>) -> Self {
+        let mut heap = MinHeap {
+            size: items.len(),
+            items,
             property: |a: &T, b: &T| -> bool { a < b },
+        };
+        heap.build_heap();
+        heap
+    }
+
+    /// Sorts `items` in ascending order by repeatedly popping the heap root.
+    pub fn heapsort(items: Vec<T>) -> Vec<T> {
+        let mut heap = Self::from_vec(items);
+        let mut sorted = Vec::with_capacity(heap.size);
+        while let Some(item) = heap.pop() {
+            sorted.push(item);
         }
+        sorted
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.items.first()
     }
 
-    fn build_heap(&mut self) -> Self {
-        unimplemented!()
+    /// Restores the heap property over the whole backing `Vec`.
+    pub fn build_heap(&mut self) {
+        for i in (0..self.size / 2).rev() {
+            self.heapify(i);
+        }
     }
 
-    fn heapify(&mut self, i: usize) -> Self {
-        unimplemented!()
+    /// Sifts the item at `i` down until both of its children satisfy the heap property.
+    pub fn heapify(&mut self, i: usize) {
+        let property = self.property;
+        sift_down_by(&mut self.items, i, &mut |a: &T, b: &T| property(a, b));
     }
+}
 
-    fn heapsort(items: Vec<T>) -> Self {
+impl<T: Ord> Heap<T> for MinHeap<T> {
+    fn new() -> Self {
         MinHeap {
-            size: items.len(),
-            items: items,
+            size: 0,
+            items: Vec::<T>::new(),
             property: |a: &T, b: &T| -> bool { a < b },
-        }.build_heap()
+        }
     }
 
     fn size(&self) -> usize {
@@ -59,20 +114,100 @@ impl<T: Default + Ord> Heap<T> for MinHeap<T> {
     }
 
     fn push(&mut self, item: T) {
-        unimplemented!()
+        let property = self.property;
+        self.items.push(item);
+        self.size += 1;
+        sift_up_by(&mut self.items, self.size - 1, &mut |a: &T, b: &T| {
+            property(a, b)
+        });
     }
 
-    fn pop(&mut self) -> T {
-        let root: T = self.items.remove(0);
-        self.build_heap();
-        root
+    fn pop(&mut self) -> Option<T> {
+        if self.size == 0 {
+            return None;
+        }
+        let root: T = self.items.swap_remove(0);
+        self.size -= 1;
+        self.heapify(0);
+        Some(root)
     }
 }
 
-impl<T: Default + Ord> Index<usize> for MinHeap<T> {
+impl<T: Ord> Index<usize> for MinHeap<T> {
     type Output = T;
 
     fn index(&self, idx: usize) -> &Self::Output {
         &self.items[idx]
     }
 }
+
+fn parent(i: usize) -> usize {
+    (i - 1) / 2
+}
+
+fn left(i: usize) -> usize {
+    (2 * i) + 1
+}
+
+fn right(i: usize) -> usize {
+    (2 * i) + 2
+}
+
+/// Moves `items[i]` towards the root while it is `less` than its parent.
+pub(crate) fn sift_up_by<T, F>(items: &mut [T], mut i: usize, less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    while i > 0 && less(&items[i], &items[parent(i)]) {
+        items.swap(i, parent(i));
+        i = parent(i);
+    }
+}
+
+/// Moves `items[i]` towards the leaves while either child is `less` than it.
+pub(crate) fn sift_down_by<T, F>(items: &mut [T], mut i: usize, less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    loop {
+        let mut top = i;
+        if left(i) < items.len() && less(&items[left(i)], &items[top]) {
+            top = left(i);
+        }
+        if right(i) < items.len() && less(&items[right(i)], &items[top]) {
+            top = right(i);
+        }
+        if top == i {
+            return;
+        }
+        items.swap(i, top);
+        i = top;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn min_heap_push_pop() {
+        let mut heap: MinHeap<i32> = MinHeap::new();
+        assert_eq!(heap.pop(), None);
+
+        for x in [5, 3, 8, 1, 9, 2].iter() {
+            heap.push(*x);
+        }
+        assert_eq!(heap.size(), 6);
+        assert_eq!(heap.peek(), Some(&1));
+
+        let popped: Vec<i32> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, vec![1, 2, 3, 5, 8, 9]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn min_heap_heapsort() {
+        let sorted = MinHeap::heapsort(vec![7, 2, 9, 4, 4, 0, -3]);
+        assert_eq!(sorted, vec![-3, 0, 2, 4, 4, 7, 9]);
+    }
+}
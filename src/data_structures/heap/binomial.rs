@@ -0,0 +1,271 @@
+//! Binomial heap.
+//!
+//! A forest of binomial trees with at most one tree per degree, so that melding
+//! two heaps works like adding two binary numbers (CLRS, ch. 19 in the 2e).
+//! Since `decrease_key` bubbles items upwards by swapping them between nodes,
+//! every item carries a shared locator which handles use to find its node.
+
+use super::{Heap, HeapError, MergeableHeap};
+use std::cell::{Ref, RefCell};
+use std::rc::{Rc, Weak};
+
+type BinomialLink<T> = Rc<RefCell<BinomialNode<T>>>;
+type Locator<T> = RefCell<Weak<RefCell<BinomialNode<T>>>>;
+
+struct BinomialNode<T> {
+    item: T,
+    locator: Rc<Locator<T>>,
+    parent: Option<Weak<RefCell<BinomialNode<T>>>>,
+    /// Children ordered by degree; `children[i]` has degree `i`.
+    children: Vec<BinomialLink<T>>,
+}
+
+/// Reference to an item inside a `BinomialHeap`, used by `decrease_key`.
+pub struct Handle<T>(Weak<Locator<T>>);
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle(self.0.clone())
+    }
+}
+
+pub struct BinomialHeap<T> {
+    /// Root trees indexed by degree.
+    roots: Vec<Option<BinomialLink<T>>>,
+    size: usize,
+}
+
+impl<T: Ord> BinomialHeap<T> {
+    pub fn peek(&self) -> Option<Ref<'_, T>> {
+        self.min_root().map(|degree| {
+            Ref::map(self.roots[degree].as_ref().unwrap().borrow(), |node| {
+                &node.item
+            })
+        })
+    }
+
+    /// Degree of the root tree holding the smallest item.
+    fn min_root(&self) -> Option<usize> {
+        let mut min: Option<usize> = None;
+        for (degree, root) in self.roots.iter().enumerate() {
+            if let Some(root) = root {
+                let smaller = match min {
+                    Some(m) => root.borrow().item < self.roots[m].as_ref().unwrap().borrow().item,
+                    None => true,
+                };
+                if smaller {
+                    min = Some(degree);
+                }
+            }
+        }
+        min
+    }
+
+    /// Joins two trees of equal degree into one tree of the next degree.
+    fn link(a: BinomialLink<T>, b: BinomialLink<T>) -> BinomialLink<T> {
+        let (top, below) = if b.borrow().item < a.borrow().item {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        below.borrow_mut().parent = Some(Rc::downgrade(&top));
+        top.borrow_mut().children.push(below);
+        top
+    }
+
+    /// Adds a forest indexed by degree into this heap, carrying like binary addition.
+    fn merge_roots(&mut self, other: Vec<Option<BinomialLink<T>>>) {
+        let mut carry: Option<BinomialLink<T>> = None;
+        let len = self.roots.len().max(other.len());
+        self.roots.resize(len, None);
+
+        for (degree, tree) in other.into_iter().chain(std::iter::repeat(None)).enumerate() {
+            if degree >= len && carry.is_none() {
+                break;
+            }
+            if degree >= self.roots.len() {
+                self.roots.push(None);
+            }
+            let trees: Vec<BinomialLink<T>> = self.roots[degree]
+                .take()
+                .into_iter()
+                .chain(tree)
+                .chain(carry.take())
+                .collect();
+            let mut trees = trees.into_iter();
+            match (trees.next(), trees.next(), trees.next()) {
+                (Some(a), Some(b), rest) => {
+                    self.roots[degree] = rest;
+                    carry = Some(Self::link(a, b));
+                }
+                (single, _, _) => self.roots[degree] = single,
+            }
+        }
+
+        while let Some(None) = self.roots.last() {
+            self.roots.pop();
+        }
+    }
+}
+
+impl<T: Ord> Heap<T> for BinomialHeap<T> {
+    fn new() -> Self {
+        BinomialHeap {
+            roots: Vec::new(),
+            size: 0,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        self.push_with_handle(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let degree = self.min_root()?;
+        let root = self.roots[degree].take().unwrap();
+        while let Some(None) = self.roots.last() {
+            self.roots.pop();
+        }
+
+        let children: Vec<Option<BinomialLink<T>>> = root
+            .borrow_mut()
+            .children
+            .drain(..)
+            .map(|child| {
+                child.borrow_mut().parent = None;
+                Some(child)
+            })
+            .collect();
+        self.merge_roots(children);
+        self.size -= 1;
+
+        match Rc::try_unwrap(root) {
+            Ok(node) => Some(node.into_inner().item),
+            Err(_) => unreachable!("heap nodes are only strongly owned by the heap"),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<T: Ord> MergeableHeap<T> for BinomialHeap<T> {
+    type Handle = Handle<T>;
+
+    fn meld(mut self, mut other: Self) -> Self {
+        let roots = std::mem::take(&mut other.roots);
+        self.merge_roots(roots);
+        self.size += other.size;
+        self
+    }
+
+    fn push_with_handle(&mut self, item: T) -> Handle<T> {
+        let locator: Rc<Locator<T>> = Rc::new(RefCell::new(Weak::new()));
+        let node = Rc::new(RefCell::new(BinomialNode {
+            item,
+            locator: locator.clone(),
+            parent: None,
+            children: Vec::new(),
+        }));
+        *locator.borrow_mut() = Rc::downgrade(&node);
+
+        self.merge_roots(vec![Some(node)]);
+        self.size += 1;
+        Handle(Rc::downgrade(&locator))
+    }
+
+    fn decrease_key(&mut self, handle: &Handle<T>, item: T) -> Result<(), HeapError> {
+        let locator = handle.0.upgrade().ok_or(HeapError::InvalidHandle)?;
+        let mut node = locator.borrow().upgrade().ok_or(HeapError::InvalidHandle)?;
+        if item > node.borrow().item {
+            return Err(HeapError::KeyIncrease);
+        }
+        node.borrow_mut().item = item;
+
+        loop {
+            let parent = match node.borrow().parent.as_ref().and_then(|p| p.upgrade()) {
+                Some(parent) => parent,
+                None => break,
+            };
+            if parent.borrow().item <= node.borrow().item {
+                break;
+            }
+            {
+                let mut child = node.borrow_mut();
+                let mut above = parent.borrow_mut();
+                let child = &mut *child;
+                let above = &mut *above;
+                std::mem::swap(&mut child.item, &mut above.item);
+                std::mem::swap(&mut child.locator, &mut above.locator);
+                *child.locator.borrow_mut() = Rc::downgrade(&node);
+                *above.locator.borrow_mut() = Rc::downgrade(&parent);
+            }
+            node = parent;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_pop_sorted() {
+        let mut heap: BinomialHeap<i32> = BinomialHeap::new();
+        for x in [4, 9, 1, 7, 3, 3, 8, 0, 12].iter() {
+            heap.push(*x);
+        }
+        assert_eq!(*heap.peek().unwrap(), 0);
+        let popped: Vec<i32> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, vec![0, 1, 3, 3, 4, 7, 8, 9, 12]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn meld_heaps() {
+        let mut a: BinomialHeap<i32> = BinomialHeap::new();
+        let mut b: BinomialHeap<i32> = BinomialHeap::new();
+        for x in 0..13 {
+            a.push(x * 2);
+        }
+        for x in 0..7 {
+            b.push(x * 3);
+        }
+        let mut heap = a.meld(b);
+        assert_eq!(heap.size(), 20);
+
+        let popped: Vec<i32> = std::iter::from_fn(|| heap.pop()).collect();
+        let mut expected: Vec<i32> = (0..13)
+            .map(|x| x * 2)
+            .chain((0..7).map(|x| x * 3))
+            .collect();
+        expected.sort();
+        assert_eq!(popped, expected);
+    }
+
+    #[test]
+    fn decrease_key_handles() {
+        let mut heap: BinomialHeap<i32> = BinomialHeap::new();
+        let handles: Vec<Handle<i32>> = (10..26).map(|x| heap.push_with_handle(x)).collect();
+        assert_eq!(heap.pop(), Some(10));
+
+        assert_eq!(
+            heap.decrease_key(&handles[0], 0),
+            Err(HeapError::InvalidHandle)
+        );
+        assert_eq!(
+            heap.decrease_key(&handles[3], 30),
+            Err(HeapError::KeyIncrease)
+        );
+        heap.decrease_key(&handles[15], 2).unwrap();
+        heap.decrease_key(&handles[7], 1).unwrap();
+        // Handles follow their items after they have been swapped upwards.
+        heap.decrease_key(&handles[15], -1).unwrap();
+
+        assert_eq!(heap.pop(), Some(-1));
+        assert_eq!(heap.pop(), Some(1));
+        assert_eq!(heap.pop(), Some(11));
+    }
+}
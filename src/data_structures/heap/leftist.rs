@@ -0,0 +1,185 @@
+//! Persistent leftist heap.
+//!
+//! Every operation returns a new heap and leaves the original untouched; the
+//! versions share all nodes off the merge path through `Rc`, just like the
+//! persistent `LinkedList`. The leftist property (the right spine is never
+//! longer than the left one) keeps that path O(log n) long.
+
+use super::Heap;
+use std::rc::Rc;
+
+type LeftistLink<T> = Option<Rc<LeftistNode<T>>>;
+
+struct LeftistNode<T> {
+    item: T,
+    /// Length of the right spine starting at this node.
+    rank: usize,
+    left: LeftistLink<T>,
+    right: LeftistLink<T>,
+}
+
+fn rank<T>(link: &LeftistLink<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.rank)
+}
+
+#[derive(Clone)]
+pub struct LeftistHeap<T> {
+    root: LeftistLink<T>,
+    size: usize,
+}
+
+impl<T: Clone + Ord> LeftistHeap<T> {
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.root.as_ref().map(|node| &node.item)
+    }
+
+    pub fn insert(&self, item: T) -> LeftistHeap<T> {
+        let single = Some(Rc::new(LeftistNode {
+            item,
+            rank: 1,
+            left: None,
+            right: None,
+        }));
+        LeftistHeap {
+            root: Self::merge_links(&self.root, &single),
+            size: self.size + 1,
+        }
+    }
+
+    pub fn merge(&self, other: &LeftistHeap<T>) -> LeftistHeap<T> {
+        LeftistHeap {
+            root: Self::merge_links(&self.root, &other.root),
+            size: self.size + other.size,
+        }
+    }
+
+    /// Returns the smallest item along with the heap that remains without it.
+    pub fn pop_min(&self) -> Option<(T, LeftistHeap<T>)> {
+        self.root.as_ref().map(|node| {
+            let rest = LeftistHeap {
+                root: Self::merge_links(&node.left, &node.right),
+                size: self.size - 1,
+            };
+            (node.item.clone(), rest)
+        })
+    }
+
+    /// Merges along the right spines, copying only the nodes on that path.
+    fn merge_links(a: &LeftistLink<T>, b: &LeftistLink<T>) -> LeftistLink<T> {
+        match (a, b) {
+            (None, _) => b.clone(),
+            (_, None) => a.clone(),
+            (Some(x), Some(y)) => {
+                let (top, other) = if y.item < x.item { (y, a) } else { (x, b) };
+                let merged = Self::merge_links(&top.right, other);
+                let (left, right) = if rank(&top.left) >= rank(&merged) {
+                    (top.left.clone(), merged)
+                } else {
+                    (merged, top.left.clone())
+                };
+                Some(Rc::new(LeftistNode {
+                    item: top.item.clone(),
+                    rank: rank(&right) + 1,
+                    left,
+                    right,
+                }))
+            }
+        }
+    }
+}
+
+impl<T: Clone + Ord> Heap<T> for LeftistHeap<T> {
+    fn new() -> Self {
+        LeftistHeap {
+            root: None,
+            size: 0,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        *self = self.insert(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.pop_min().map(|(item, rest)| {
+            *self = rest;
+            item
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<T> Drop for LeftistHeap<T> {
+    fn drop(&mut self) {
+        // Only tear down nodes this version owns exclusively; shared subtrees
+        // stay alive for the other versions still referring to them.
+        let mut stack: Vec<Rc<LeftistNode<T>>> = self.root.take().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            if let Ok(mut node) = Rc::try_unwrap(node) {
+                stack.extend(node.left.take());
+                stack.extend(node.right.take());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_pop_sorted() {
+        let heap: LeftistHeap<i32> = LeftistHeap::new();
+        let heap = [6, 2, 9, 2, 7, 1].iter().fold(heap, |h, x| h.insert(*x));
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.peek_min(), Some(&1));
+
+        let mut popped = Vec::new();
+        let mut current = heap;
+        while let Some((item, rest)) = current.pop_min() {
+            popped.push(item);
+            current = rest;
+        }
+        assert_eq!(popped, vec![1, 2, 2, 6, 7, 9]);
+    }
+
+    #[test]
+    fn versions_are_independent() {
+        let base = LeftistHeap::new().insert(5).insert(3).insert(8);
+        let (min, without_min) = base.pop_min().unwrap();
+        let extended = base.insert(1);
+
+        assert_eq!(min, 3);
+        assert_eq!(base.peek_min(), Some(&3));
+        assert_eq!(base.len(), 3);
+        assert_eq!(without_min.peek_min(), Some(&5));
+        assert_eq!(extended.peek_min(), Some(&1));
+    }
+
+    #[test]
+    fn merge_heaps() {
+        let a = (0..10)
+            .step_by(2)
+            .fold(LeftistHeap::new(), |h, x| h.insert(x));
+        let b = (1..10)
+            .step_by(2)
+            .fold(LeftistHeap::new(), |h, x| h.insert(x));
+        let mut merged = a.merge(&b);
+        assert_eq!(merged.size(), 10);
+        assert_eq!(a.len(), 5);
+
+        let popped: Vec<i32> = std::iter::from_fn(|| merged.pop()).collect();
+        assert_eq!(popped, (0..10).collect::<Vec<i32>>());
+    }
+}
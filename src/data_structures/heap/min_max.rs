@@ -0,0 +1,231 @@
+//! Min-max heap (double-ended priority queue).
+//!
+//! A single array-backed complete binary tree whose even levels are ordered
+//! like a min-heap and whose odd levels are ordered like a max-heap (Atkinson
+//! et al., 1986). The smallest item is the root and the largest is one of its
+//! children, so both ends can be peeked in O(1) and popped in O(log n).
+
+use super::{parent, Heap};
+use std::vec::Vec;
+
+pub struct MinMaxHeap<T> {
+    items: Vec<T>,
+    /// Maximum number of items kept in bounded mode.
+    capacity: Option<usize>,
+}
+
+fn is_min_level(i: usize) -> bool {
+    (i + 1).ilog2().is_multiple_of(2)
+}
+
+impl<T: Ord> MinMaxHeap<T> {
+    /// Creates a heap which never holds more than `capacity` items, evicting
+    /// the largest item whenever an insertion would overflow it.
+    pub fn bounded(capacity: usize) -> Self {
+        MinMaxHeap {
+            items: Vec::with_capacity(capacity),
+            capacity: Some(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.items.first()
+    }
+
+    pub fn peek_max(&self) -> Option<&T> {
+        self.max_index().map(|i| &self.items[i])
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.remove_at(0)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        self.max_index().and_then(|i| self.remove_at(i))
+    }
+
+    /// Inserts `item`, returning whatever had to be evicted to stay within the
+    /// bound: either the previous maximum or `item` itself when it is no
+    /// smaller than everything already kept. Unbounded heaps never evict.
+    pub fn insert(&mut self, item: T) -> Option<T> {
+        if let Some(capacity) = self.capacity {
+            if self.items.len() >= capacity {
+                match self.peek_max() {
+                    Some(max) if item < *max => {}
+                    _ => return Some(item),
+                }
+                let evicted = self.pop_max();
+                self.push_unbounded(item);
+                return evicted;
+            }
+        }
+        self.push_unbounded(item);
+        None
+    }
+
+    /// Consumes the heap, returning its items in ascending order.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        std::iter::from_fn(|| self.pop_min()).collect()
+    }
+
+    fn push_unbounded(&mut self, item: T) {
+        self.items.push(item);
+        self.bubble_up(self.items.len() - 1);
+    }
+
+    fn max_index(&self) -> Option<usize> {
+        match self.items.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ if self.items[2] > self.items[1] => Some(2),
+            _ => Some(1),
+        }
+    }
+
+    fn remove_at(&mut self, i: usize) -> Option<T> {
+        if i >= self.items.len() {
+            return None;
+        }
+        let item = self.items.swap_remove(i);
+        if i < self.items.len() {
+            self.trickle_down(i);
+        }
+        Some(item)
+    }
+
+    /// Whether `a` belongs above `b` on a level of the given kind.
+    fn before(&self, a: usize, b: usize, min_level: bool) -> bool {
+        if min_level {
+            self.items[a] < self.items[b]
+        } else {
+            self.items[a] > self.items[b]
+        }
+    }
+
+    fn bubble_up(&mut self, i: usize) {
+        if i == 0 {
+            return;
+        }
+        let min_level = is_min_level(i);
+        let p = parent(i);
+        if self.before(p, i, min_level) {
+            self.items.swap(i, p);
+            self.bubble_up_grandparents(p, !min_level);
+        } else {
+            self.bubble_up_grandparents(i, min_level);
+        }
+    }
+
+    fn bubble_up_grandparents(&mut self, mut i: usize, min_level: bool) {
+        while i > 2 {
+            let grandparent = parent(parent(i));
+            if !self.before(i, grandparent, min_level) {
+                break;
+            }
+            self.items.swap(i, grandparent);
+            i = grandparent;
+        }
+    }
+
+    fn trickle_down(&mut self, mut i: usize) {
+        let min_level = is_min_level(i);
+        loop {
+            let first_child = 2 * i + 1;
+            if first_child >= self.items.len() {
+                return;
+            }
+
+            let first_grandchild = 2 * first_child + 1;
+            let descendants = (first_child..first_child + 2)
+                .chain(first_grandchild..first_grandchild + 4)
+                .filter(|&d| d < self.items.len());
+            let mut m = first_child;
+            for d in descendants {
+                if self.before(d, m, min_level) {
+                    m = d;
+                }
+            }
+
+            if !self.before(m, i, min_level) {
+                return;
+            }
+            self.items.swap(m, i);
+            if m < first_grandchild {
+                return;
+            }
+            if self.before(parent(m), m, min_level) {
+                self.items.swap(m, parent(m));
+            }
+            i = m;
+        }
+    }
+}
+
+impl<T: Ord> Heap<T> for MinMaxHeap<T> {
+    fn new() -> Self {
+        MinMaxHeap {
+            items: Vec::new(),
+            capacity: None,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        self.insert(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.pop_min()
+    }
+
+    fn size(&self) -> usize {
+        self.items.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pop_both_ends() {
+        let mut heap: MinMaxHeap<i32> = MinMaxHeap::new();
+        let items = [15, 3, 42, 8, 23, 4, 16, 1, 99, 7, 7, 60];
+        for x in items.iter() {
+            heap.push(*x);
+        }
+        let mut sorted = items.to_vec();
+        sorted.sort();
+
+        assert_eq!(heap.peek_min(), Some(&1));
+        assert_eq!(heap.peek_max(), Some(&99));
+
+        let mut mins = Vec::new();
+        let mut maxs = Vec::new();
+        while !heap.is_empty() {
+            mins.extend(heap.pop_min());
+            maxs.extend(heap.pop_max());
+        }
+        maxs.reverse();
+        mins.extend(maxs);
+        assert_eq!(mins, sorted);
+    }
+
+    #[test]
+    fn bounded_keeps_smallest() {
+        let mut heap: MinMaxHeap<i32> = MinMaxHeap::bounded(3);
+        assert_eq!(heap.insert(5), None);
+        assert_eq!(heap.insert(9), None);
+        assert_eq!(heap.insert(2), None);
+        assert_eq!(heap.insert(7), Some(9));
+        assert_eq!(heap.insert(11), Some(11));
+        heap.push(1);
+
+        assert_eq!(heap.size(), 3);
+        assert_eq!(heap.into_sorted_vec(), vec![1, 2, 5]);
+    }
+}
@@ -0,0 +1,260 @@
+//! Pairing heap.
+//!
+//! A heap-ordered multiway tree where `meld` simply links two roots, and
+//! `pop` restructures the children of the old root with the two-pass pairing
+//! scheme. Nodes are shared behind `Rc<RefCell<_>>` so that handles handed
+//! out by `push_with_handle` can find their node again for `decrease_key`.
+
+use super::{Heap, HeapError, MergeableHeap};
+use std::cell::{Ref, RefCell};
+use std::rc::{Rc, Weak};
+
+type PairLink<T> = Option<Rc<RefCell<PairNode<T>>>>;
+
+struct PairNode<T> {
+    item: T,
+    /// Leftmost child.
+    child: PairLink<T>,
+    /// Next sibling to the right.
+    sibling: PairLink<T>,
+    /// Parent if this is the leftmost child, otherwise the left sibling.
+    prev: Option<Weak<RefCell<PairNode<T>>>>,
+}
+
+/// Reference to an item inside a `PairingHeap`, used by `decrease_key`.
+pub struct Handle<T>(Weak<RefCell<PairNode<T>>>);
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle(self.0.clone())
+    }
+}
+
+pub struct PairingHeap<T> {
+    root: PairLink<T>,
+    size: usize,
+}
+
+impl<T: Ord> PairingHeap<T> {
+    pub fn peek(&self) -> Option<Ref<'_, T>> {
+        self.root
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.item))
+    }
+
+    /// Makes the root with the larger item the leftmost child of the other.
+    fn link(a: Rc<RefCell<PairNode<T>>>, b: Rc<RefCell<PairNode<T>>>) -> Rc<RefCell<PairNode<T>>> {
+        let (top, below) = if b.borrow().item < a.borrow().item {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        {
+            let mut top_node = top.borrow_mut();
+            let mut below_node = below.borrow_mut();
+            if let Some(first) = top_node.child.take() {
+                first.borrow_mut().prev = Some(Rc::downgrade(&below));
+                below_node.sibling = Some(first);
+            }
+            below_node.prev = Some(Rc::downgrade(&top));
+        }
+        top.borrow_mut().child = Some(below);
+        top
+    }
+
+    fn merge_root(&mut self, node: Rc<RefCell<PairNode<T>>>) {
+        self.root = Some(match self.root.take() {
+            Some(root) => Self::link(root, node),
+            None => node,
+        });
+    }
+
+    /// Detaches `node` (and its subtree) from its parent and siblings.
+    fn cut(node: &Rc<RefCell<PairNode<T>>>) {
+        let mut cut_node = node.borrow_mut();
+        let prev = match cut_node.prev.take().and_then(|prev| prev.upgrade()) {
+            Some(prev) => prev,
+            None => return,
+        };
+        let sibling = cut_node.sibling.take();
+        if let Some(sibling) = sibling.as_ref() {
+            sibling.borrow_mut().prev = Some(Rc::downgrade(&prev));
+        }
+        let mut prev_node = prev.borrow_mut();
+        let is_leftmost = prev_node
+            .child
+            .as_ref()
+            .is_some_and(|child| Rc::ptr_eq(child, node));
+        if is_leftmost {
+            prev_node.child = sibling;
+        } else {
+            prev_node.sibling = sibling;
+        }
+    }
+
+    /// Merges a detached child list with the two-pass pairing scheme.
+    fn pair_children(first: PairLink<T>) -> PairLink<T> {
+        let mut children = Vec::new();
+        let mut current = first;
+        while let Some(node) = current {
+            current = {
+                let mut child = node.borrow_mut();
+                child.prev = None;
+                child.sibling.take()
+            };
+            children.push(node);
+        }
+
+        let mut paired = Vec::with_capacity(children.len().div_ceil(2));
+        let mut children = children.into_iter();
+        while let Some(a) = children.next() {
+            paired.push(match children.next() {
+                Some(b) => Self::link(a, b),
+                None => a,
+            });
+        }
+
+        paired.into_iter().rev().reduce(Self::link)
+    }
+}
+
+impl<T: Ord> Heap<T> for PairingHeap<T> {
+    fn new() -> Self {
+        PairingHeap {
+            root: None,
+            size: 0,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        self.push_with_handle(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.root.take().map(|root| {
+            let child = root.borrow_mut().child.take();
+            self.root = Self::pair_children(child);
+            self.size -= 1;
+            match Rc::try_unwrap(root) {
+                Ok(node) => node.into_inner().item,
+                Err(_) => unreachable!("heap nodes are only strongly owned by the heap"),
+            }
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<T: Ord> MergeableHeap<T> for PairingHeap<T> {
+    type Handle = Handle<T>;
+
+    fn meld(mut self, mut other: Self) -> Self {
+        if let Some(root) = other.root.take() {
+            self.merge_root(root);
+        }
+        self.size += other.size;
+        self
+    }
+
+    fn push_with_handle(&mut self, item: T) -> Handle<T> {
+        let node = Rc::new(RefCell::new(PairNode {
+            item,
+            child: None,
+            sibling: None,
+            prev: None,
+        }));
+        let handle = Handle(Rc::downgrade(&node));
+        self.merge_root(node);
+        self.size += 1;
+        handle
+    }
+
+    fn decrease_key(&mut self, handle: &Handle<T>, item: T) -> Result<(), HeapError> {
+        let node = handle.0.upgrade().ok_or(HeapError::InvalidHandle)?;
+        if item > node.borrow().item {
+            return Err(HeapError::KeyIncrease);
+        }
+        node.borrow_mut().item = item;
+
+        let is_root = self
+            .root
+            .as_ref()
+            .is_some_and(|root| Rc::ptr_eq(root, &node));
+        if !is_root {
+            Self::cut(&node);
+            self.merge_root(node);
+        }
+        Ok(())
+    }
+}
+
+impl<T> Drop for PairingHeap<T> {
+    fn drop(&mut self) {
+        // Unlink iteratively; long sibling chains would otherwise overflow the
+        // stack through recursive `Rc` drops.
+        let mut stack: Vec<Rc<RefCell<PairNode<T>>>> = self.root.take().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let mut node = node.borrow_mut();
+            stack.extend(node.child.take());
+            stack.extend(node.sibling.take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_pop_sorted() {
+        let mut heap: PairingHeap<i32> = PairingHeap::new();
+        for x in [4, 9, 1, 7, 3, 3, 8].iter() {
+            heap.push(*x);
+        }
+        assert_eq!(*heap.peek().unwrap(), 1);
+        let popped: Vec<i32> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, vec![1, 3, 3, 4, 7, 8, 9]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn meld_heaps() {
+        let mut a: PairingHeap<i32> = PairingHeap::new();
+        let mut b: PairingHeap<i32> = PairingHeap::new();
+        for x in 0..10 {
+            if x % 2 == 0 {
+                a.push(x);
+            } else {
+                b.push(x);
+            }
+        }
+        let mut heap = a.meld(b);
+        assert_eq!(heap.size(), 10);
+        let popped: Vec<i32> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, (0..10).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn decrease_key_handles() {
+        let mut heap: PairingHeap<i32> = PairingHeap::new();
+        let handles: Vec<Handle<i32>> = (10..20).map(|x| heap.push_with_handle(x)).collect();
+        heap.pop();
+
+        assert_eq!(
+            heap.decrease_key(&handles[0], 0),
+            Err(HeapError::InvalidHandle)
+        );
+        assert_eq!(
+            heap.decrease_key(&handles[5], 30),
+            Err(HeapError::KeyIncrease)
+        );
+        heap.decrease_key(&handles[5], 1).unwrap();
+        heap.decrease_key(&handles[9], 5).unwrap();
+
+        assert_eq!(heap.pop(), Some(1));
+        assert_eq!(heap.pop(), Some(5));
+        assert_eq!(heap.pop(), Some(11));
+    }
+}
@@ -0,0 +1,126 @@
+//! Radix heap for monotone integer priorities.
+//!
+//! Items are bucketed by the highest bit in which their key differs from the
+//! last popped key. Popping only rescans a bucket once it is the lowest
+//! non-empty one, after which every item in it moves to a strictly lower
+//! bucket, so each item is touched O(log C) times over its lifetime, where C is
+//! the range of keys. This relies on keys never dropping below the last popped
+//! key, which `push` enforces.
+
+use super::HeapError;
+use num_traits::PrimInt;
+use std::vec::Vec;
+
+pub struct RadixHeap<P, V> {
+    /// `buckets[0]` holds keys equal to `last`, `buckets[i]` keys whose highest
+    /// bit differing from `last` is bit `i - 1`.
+    buckets: Vec<Vec<(P, V)>>,
+    last: P,
+    size: usize,
+}
+
+impl<P: PrimInt, V> RadixHeap<P, V> {
+    pub fn new() -> Self {
+        let bits = P::zero().count_zeros() as usize;
+        RadixHeap {
+            buckets: (0..=bits).map(|_| Vec::new()).collect(),
+            last: P::min_value(),
+            size: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// The most recently popped key; no key below it can be pushed.
+    pub fn last_key(&self) -> P {
+        self.last
+    }
+
+    pub fn push(&mut self, key: P, value: V) -> Result<(), HeapError> {
+        if key < self.last {
+            return Err(HeapError::NonMonotone);
+        }
+        let bucket = self.bucket(key);
+        self.buckets[bucket].push((key, value));
+        self.size += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<(P, V)> {
+        if self.size == 0 {
+            return None;
+        }
+        if self.buckets[0].is_empty() {
+            let i = self.buckets.iter().position(|b| !b.is_empty())?;
+            let items = std::mem::take(&mut self.buckets[i]);
+            self.last = items.iter().map(|(key, _)| *key).min()?;
+            for (key, value) in items {
+                let bucket = self.bucket(key);
+                self.buckets[bucket].push((key, value));
+            }
+        }
+        self.size -= 1;
+        self.buckets[0].pop()
+    }
+
+    fn bucket(&self, key: P) -> usize {
+        if key == self.last {
+            0
+        } else {
+            self.buckets.len() - 1 - (key ^ self.last).leading_zeros() as usize
+        }
+    }
+}
+
+impl<P: PrimInt, V> Default for RadixHeap<P, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pops_in_key_order() {
+        let mut heap: RadixHeap<u32, char> = RadixHeap::new();
+        for (key, value) in [(7, 'a'), (3, 'b'), (1024, 'c'), (3, 'd'), (0, 'e')].iter() {
+            heap.push(*key, *value).unwrap();
+        }
+        let keys: Vec<u32> = std::iter::from_fn(|| heap.pop()).map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![0, 3, 3, 7, 1024]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn interleaved_monotone_pushes() {
+        let mut heap: RadixHeap<i64, ()> = RadixHeap::new();
+        heap.push(-20, ()).unwrap();
+        heap.push(15, ()).unwrap();
+        assert_eq!(heap.pop().map(|(k, _)| k), Some(-20));
+        heap.push(-5, ()).unwrap();
+        heap.push(-20, ()).unwrap();
+        assert_eq!(heap.pop().map(|(k, _)| k), Some(-20));
+        assert_eq!(heap.pop().map(|(k, _)| k), Some(-5));
+        assert_eq!(heap.last_key(), -5);
+        assert_eq!(heap.pop().map(|(k, _)| k), Some(15));
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn rejects_keys_below_last_pop() {
+        let mut heap: RadixHeap<u8, &str> = RadixHeap::new();
+        heap.push(10, "a").unwrap();
+        heap.push(12, "b").unwrap();
+        heap.pop();
+        assert_eq!(heap.push(9, "c"), Err(HeapError::NonMonotone));
+        assert_eq!(heap.len(), 1);
+    }
+}
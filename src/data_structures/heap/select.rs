@@ -0,0 +1,245 @@
+//! Heap-backed selection and merging of iterators.
+//!
+//! `top_k` and `bottom_k` keep a heap of at most `k` items, so they run in
+//! O(n log k) time and O(k) memory regardless of the input length. `kmerge`
+//! lazily merges already sorted iterators by keeping their heads in a heap.
+
+use super::{sift_down_by, sift_up_by};
+use std::cmp::Ordering;
+use std::iter::FusedIterator;
+use std::vec::Vec;
+
+/// The `k` largest items, largest first.
+pub fn top_k<I>(iter: I, k: usize) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    top_k_by(iter, k, Ord::cmp)
+}
+
+/// The `k` largest items according to `compare`, largest first.
+pub fn top_k_by<I, F>(iter: I, k: usize, mut compare: F) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    let mut kept = select_by(iter, k, &mut compare);
+    kept.sort_by(|a, b| compare(b, a));
+    kept
+}
+
+/// The `k` items with the largest keys, largest first.
+pub fn top_k_by_key<I, K, F>(iter: I, k: usize, mut key: F) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    K: Ord,
+    F: FnMut(&I::Item) -> K,
+{
+    top_k_by(iter, k, |a, b| key(a).cmp(&key(b)))
+}
+
+/// The `k` smallest items, smallest first.
+pub fn bottom_k<I>(iter: I, k: usize) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    bottom_k_by(iter, k, Ord::cmp)
+}
+
+/// The `k` smallest items according to `compare`, smallest first.
+pub fn bottom_k_by<I, F>(iter: I, k: usize, mut compare: F) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    let mut kept = select_by(iter, k, &mut |a, b| compare(b, a));
+    kept.sort_by(|a, b| compare(a, b));
+    kept
+}
+
+/// The `k` items with the smallest keys, smallest first.
+pub fn bottom_k_by_key<I, K, F>(iter: I, k: usize, mut key: F) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    K: Ord,
+    F: FnMut(&I::Item) -> K,
+{
+    bottom_k_by(iter, k, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Keeps the `k` greatest items in a heap rooted at the least of them.
+fn select_by<I, F>(iter: I, k: usize, compare: &mut F) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    let mut kept: Vec<I::Item> = Vec::with_capacity(k);
+    if k == 0 {
+        return kept;
+    }
+    let mut less = |a: &I::Item, b: &I::Item| compare(a, b) == Ordering::Less;
+
+    for item in iter {
+        if kept.len() < k {
+            kept.push(item);
+            let last = kept.len() - 1;
+            sift_up_by(&mut kept, last, &mut less);
+        } else if less(&kept[0], &item) {
+            kept[0] = item;
+            sift_down_by(&mut kept, 0, &mut less);
+        }
+    }
+    kept
+}
+
+struct HeadTail<I: Iterator> {
+    head: I::Item,
+    tail: I,
+}
+
+/// Iterator returned by `kmerge` and its variants.
+pub struct KMerge<I: Iterator, F> {
+    heap: Vec<HeadTail<I>>,
+    compare: F,
+}
+
+/// Comparator used by the plain `kmerge`.
+pub type OrdCmp<T> = fn(&T, &T) -> Ordering;
+
+/// Lazily merges iterators which are each sorted in ascending order.
+pub fn kmerge<T, I>(iters: T) -> KMerge<I::IntoIter, OrdCmp<I::Item>>
+where
+    T: IntoIterator<Item = I>,
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    kmerge_by(iters, Ord::cmp)
+}
+
+/// Lazily merges iterators which are each sorted according to `compare`.
+pub fn kmerge_by<T, I, F>(iters: T, mut compare: F) -> KMerge<I::IntoIter, F>
+where
+    T: IntoIterator<Item = I>,
+    I: IntoIterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    let mut heap: Vec<HeadTail<I::IntoIter>> = Vec::new();
+    for iter in iters {
+        let mut tail = iter.into_iter();
+        if let Some(head) = tail.next() {
+            heap.push(HeadTail { head, tail });
+            let last = heap.len() - 1;
+            sift_up_by(
+                &mut heap,
+                last,
+                &mut |a: &HeadTail<I::IntoIter>, b: &HeadTail<I::IntoIter>| {
+                    compare(&a.head, &b.head) == Ordering::Less
+                },
+            );
+        }
+    }
+    KMerge { heap, compare }
+}
+
+/// Lazily merges iterators which are each sorted by the key `key` extracts.
+pub fn kmerge_by_key<T, I, K, F>(
+    iters: T,
+    mut key: F,
+) -> KMerge<I::IntoIter, impl FnMut(&I::Item, &I::Item) -> Ordering>
+where
+    T: IntoIterator<Item = I>,
+    I: IntoIterator,
+    K: Ord,
+    F: FnMut(&I::Item) -> K,
+{
+    kmerge_by(iters, move |a: &I::Item, b: &I::Item| key(a).cmp(&key(b)))
+}
+
+impl<I, F> Iterator for KMerge<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        let item = match self.heap[0].tail.next() {
+            Some(next) => std::mem::replace(&mut self.heap[0].head, next),
+            None => self.heap.swap_remove(0).head,
+        };
+
+        let compare = &mut self.compare;
+        sift_down_by(
+            &mut self.heap,
+            0,
+            &mut |a: &HeadTail<I>, b: &HeadTail<I>| compare(&a.head, &b.head) == Ordering::Less,
+        );
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.heap.iter().fold((0, Some(0)), |(low, high), ht| {
+            let (tail_low, tail_high) = ht.tail.size_hint();
+            let high = match (high, tail_high) {
+                (Some(h), Some(t)) => h.checked_add(t).and_then(|s| s.checked_add(1)),
+                _ => None,
+            };
+            (low.saturating_add(tail_low).saturating_add(1), high)
+        })
+    }
+}
+
+impl<I, F> FusedIterator for KMerge<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn top_and_bottom_k() {
+        let items = vec![14, 3, 27, 8, 27, 1, 19, 5, 0, 11];
+        assert_eq!(top_k(items.clone(), 3), vec![27, 27, 19]);
+        assert_eq!(bottom_k(items.clone(), 4), vec![0, 1, 3, 5]);
+        assert_eq!(top_k(items.clone(), 0), Vec::<i32>::new());
+        assert_eq!(bottom_k(items.iter().cloned(), 20).len(), items.len());
+    }
+
+    #[test]
+    fn top_k_by_key_variants() {
+        let words = vec!["pear", "fig", "banana", "kiwi", "apricot"];
+        assert_eq!(
+            top_k_by_key(words.clone(), 2, |w| w.len()),
+            vec!["apricot", "banana"]
+        );
+        assert_eq!(bottom_k_by_key(words.clone(), 1, |w| w.len()), vec!["fig"]);
+        assert_eq!(
+            top_k_by(words, 2, |a, b| b.cmp(a)),
+            vec!["apricot", "banana"]
+        );
+    }
+
+    #[test]
+    fn kmerge_sorted_iterators() {
+        let merged: Vec<i32> =
+            kmerge(vec![vec![1, 4, 9], vec![], vec![2, 3, 10, 11], vec![0]]).collect();
+        assert_eq!(merged, vec![0, 1, 2, 3, 4, 9, 10, 11]);
+
+        let descending: Vec<i32> =
+            kmerge_by(vec![vec![9, 4, 1], vec![8, 2]], |a, b| b.cmp(a)).collect();
+        assert_eq!(descending, vec![9, 8, 4, 2, 1]);
+
+        let by_key: Vec<(char, i32)> =
+            kmerge_by_key(vec![vec![('a', 1), ('c', 5)], vec![('b', 2)]], |p| p.1).collect();
+        assert_eq!(by_key, vec![('a', 1), ('b', 2), ('c', 5)]);
+    }
+}
@@ -1 +1,3 @@
+pub mod dag;
+pub mod disjoint_set;
 pub mod tree;
@@ -0,0 +1,393 @@
+//! Directed acyclic graphs.
+//!
+//! Built on `tree::Node`, whose `parents` list lets a node hang below several
+//! others. Edges point from parent to child, and any edge that would close a
+//! cycle is rejected up front, so every `Dag` is acyclic by construction.
+
+use super::tree::Node;
+use snafu::Snafu;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap};
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum DagError {
+    #[snafu(display("No node with id {} exists in the graph", id))]
+    NodeNotFound { id: usize },
+    #[snafu(display("A node with id {} already exists in the graph", id))]
+    DuplicateNode { id: usize },
+    #[snafu(display("Edge {} -> {} would create the cycle {:?}", from, to, cycle))]
+    Cycle {
+        from: usize,
+        to: usize,
+        cycle: Vec<usize>,
+    },
+}
+
+/// Directed acyclic graph whose nodes are addressed by their unique ids.
+pub struct Dag {
+    nodes: BTreeMap<usize, Rc<Node>>,
+}
+
+impl Dag {
+    pub fn new() -> Self {
+        Dag {
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.nodes.contains_key(&id)
+    }
+
+    pub fn add_node(&mut self, id: usize) -> Result<(), DagError> {
+        if self.nodes.contains_key(&id) {
+            return Err(DagError::DuplicateNode { id });
+        }
+        self.nodes.insert(id, Node::new(id));
+        Ok(())
+    }
+
+    /// Adds the edge `from -> to`, failing if `from` is reachable from `to`.
+    /// Adding an edge which already exists does nothing.
+    pub fn add_edge(&mut self, from: usize, to: usize) -> Result<(), DagError> {
+        let parent = self.get(from)?.clone();
+        let child = self.get(to)?.clone();
+        if let Some(mut path) = self.path(to, from) {
+            path.insert(0, from);
+            return Err(DagError::Cycle {
+                from,
+                to,
+                cycle: path,
+            });
+        }
+        if self.has_edge(from, to) {
+            return Ok(());
+        }
+        child.parents.borrow_mut().push(Rc::downgrade(&parent));
+        parent.children.borrow_mut().push(child);
+        Ok(())
+    }
+
+    /// Removes the edge `from -> to`, returning whether it existed.
+    pub fn remove_edge(&mut self, from: usize, to: usize) -> bool {
+        let (parent, child) = match (self.nodes.get(&from), self.nodes.get(&to)) {
+            (Some(parent), Some(child)) => (parent, child),
+            _ => return false,
+        };
+        let mut children = parent.children.borrow_mut();
+        let before = children.len();
+        children.retain(|c| !Rc::ptr_eq(c, child));
+        child
+            .parents
+            .borrow_mut()
+            .retain(|p| !std::ptr::eq(p.as_ptr(), Rc::as_ptr(parent)));
+        children.len() != before
+    }
+
+    pub fn has_edge(&self, from: usize, to: usize) -> bool {
+        self.nodes
+            .get(&from)
+            .is_some_and(|node| node.children.borrow().iter().any(|child| child.id == to))
+    }
+
+    pub fn children(&self, id: usize) -> Vec<usize> {
+        self.nodes.get(&id).map_or_else(Vec::new, |node| {
+            node.children
+                .borrow()
+                .iter()
+                .map(|child| child.id)
+                .collect()
+        })
+    }
+
+    pub fn parents(&self, id: usize) -> Vec<usize> {
+        self.nodes.get(&id).map_or_else(Vec::new, |node| {
+            node.parents
+                .borrow()
+                .iter()
+                .filter_map(|parent| parent.upgrade())
+                .map(|parent| parent.id)
+                .collect()
+        })
+    }
+
+    /// Nodes without any incoming edges.
+    pub fn roots(&self) -> Vec<usize> {
+        self.nodes
+            .values()
+            .filter(|node| node.parents.borrow().is_empty())
+            .map(|node| node.id)
+            .collect()
+    }
+
+    /// Topological order by repeatedly removing nodes without remaining parents
+    /// (Kahn, 1962). Ties are broken by ascending id.
+    pub fn topological_sort_kahn(&self) -> Vec<usize> {
+        let mut in_degree: HashMap<usize, usize> = self
+            .nodes
+            .values()
+            .map(|node| (node.id, node.parents.borrow().len()))
+            .collect();
+        // Min-heap of the ids whose parents have all been emitted.
+        let mut ready: BinaryHeap<Reverse<usize>> = self
+            .nodes
+            .keys()
+            .filter(|id| in_degree[id] == 0)
+            .map(|id| Reverse(*id))
+            .collect();
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(Reverse(id)) = ready.pop() {
+            order.push(id);
+            for child in self.nodes[&id].children.borrow().iter() {
+                let degree = in_degree.get_mut(&child.id).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push(Reverse(child.id));
+                }
+            }
+        }
+        order
+    }
+
+    /// Topological order as the reverse of a depth-first post-order (CLRS 22.4).
+    pub fn topological_sort_dfs(&self) -> Vec<usize> {
+        let mut visited: BTreeSet<usize> = BTreeSet::new();
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        for root in self.nodes.values() {
+            if !visited.insert(root.id) {
+                continue;
+            }
+            let mut stack: Vec<(Rc<Node>, usize)> = vec![(root.clone(), 0)];
+            while let Some((node, next_child)) = stack.last_mut() {
+                let child = node.children.borrow().get(*next_child).cloned();
+                match child {
+                    Some(child) => {
+                        *next_child += 1;
+                        if visited.insert(child.id) {
+                            stack.push((child, 0));
+                        }
+                    }
+                    None => {
+                        order.push(node.id);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// All nodes from which `id` can be reached.
+    pub fn ancestors(&self, id: usize) -> BTreeSet<usize> {
+        self.reachable(id, |node| {
+            node.parents
+                .borrow()
+                .iter()
+                .filter_map(|parent| parent.upgrade())
+                .collect()
+        })
+    }
+
+    /// All nodes which can be reached from `id`.
+    pub fn descendants(&self, id: usize) -> BTreeSet<usize> {
+        self.reachable(id, |node| node.children.borrow().clone())
+    }
+
+    /// The smallest graph with the same reachability, keeping only the edges
+    /// `u -> v` for which there is no longer path from `u` to `v`.
+    pub fn transitive_reduction(&self) -> Dag {
+        let mut reduced = Dag::new();
+        for id in self.nodes.keys() {
+            reduced.nodes.insert(*id, Node::new(*id));
+        }
+
+        for node in self.nodes.values() {
+            let children = self.children(node.id);
+            let indirect: BTreeSet<usize> = children
+                .iter()
+                .flat_map(|child| self.descendants(*child))
+                .collect();
+            for child in children.into_iter().filter(|c| !indirect.contains(c)) {
+                let parent = &reduced.nodes[&node.id];
+                let child = &reduced.nodes[&child];
+                child.parents.borrow_mut().push(Rc::downgrade(parent));
+                parent.children.borrow_mut().push(child.clone());
+            }
+        }
+        reduced
+    }
+
+    fn get(&self, id: usize) -> Result<&Rc<Node>, DagError> {
+        self.nodes.get(&id).ok_or(DagError::NodeNotFound { id })
+    }
+
+    /// A path of ids from `from` to `to` following child edges, if one exists.
+    fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut came_from: HashMap<usize, usize> = HashMap::new();
+        let mut stack = vec![self.nodes.get(&from)?.clone()];
+        while let Some(node) = stack.pop() {
+            if node.id == to {
+                let mut path = vec![to];
+                while let Some(prev) = came_from.get(path.last().unwrap()) {
+                    path.push(*prev);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for child in node.children.borrow().iter() {
+                if child.id != from && !came_from.contains_key(&child.id) {
+                    came_from.insert(child.id, node.id);
+                    stack.push(child.clone());
+                }
+            }
+        }
+        None
+    }
+
+    fn reachable<F>(&self, id: usize, neighbours: F) -> BTreeSet<usize>
+    where
+        F: Fn(&Node) -> Vec<Rc<Node>>,
+    {
+        let mut seen = BTreeSet::new();
+        let mut stack: Vec<Rc<Node>> = self.nodes.get(&id).into_iter().cloned().collect();
+        while let Some(node) = stack.pop() {
+            for next in neighbours(&node) {
+                if seen.insert(next.id) {
+                    stack.push(next);
+                }
+            }
+        }
+        seen
+    }
+}
+
+impl Default for Dag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Dag {
+    fn drop(&mut self) {
+        // A node is held by `nodes` and by each of its parents, possibly
+        // several, and is freed by whichever owner lets go last. Cutting every
+        // edge first leaves `nodes` as the only owner, so nodes are freed one
+        // at a time instead of by recursing down long paths.
+        for node in self.nodes.values() {
+            node.children.borrow_mut().clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //   0 -> 1 -> 3 -> 4
+    //   0 -> 2 -> 3
+    //   0 -------> 3
+    //   1 ------------> 4
+    fn setup_dag() -> Dag {
+        let mut dag = Dag::new();
+        for id in 0..5 {
+            dag.add_node(id).unwrap();
+        }
+        for (from, to) in [(0, 1), (0, 2), (1, 3), (2, 3), (3, 4), (0, 3), (1, 4)].iter() {
+            dag.add_edge(*from, *to).unwrap();
+        }
+        dag
+    }
+
+    fn assert_topological(dag: &Dag, order: &[usize]) {
+        assert_eq!(order.len(), dag.size());
+        let position: HashMap<usize, usize> =
+            order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        for id in order.iter() {
+            for child in dag.children(*id) {
+                assert!(position[id] < position[&child]);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let mut dag = setup_dag();
+        assert_eq!(
+            dag.add_edge(4, 0),
+            Err(DagError::Cycle {
+                from: 4,
+                to: 0,
+                cycle: vec![4, 0, 3, 4],
+            })
+        );
+        assert_eq!(
+            dag.add_edge(2, 2),
+            Err(DagError::Cycle {
+                from: 2,
+                to: 2,
+                cycle: vec![2, 2],
+            })
+        );
+        assert_eq!(dag.add_edge(0, 9), Err(DagError::NodeNotFound { id: 9 }));
+        assert!(dag.add_edge(2, 4).is_ok());
+    }
+
+    #[test]
+    fn topological_orders() {
+        let dag = setup_dag();
+        assert_eq!(dag.roots(), vec![0]);
+        assert_topological(&dag, &dag.topological_sort_kahn());
+        assert_topological(&dag, &dag.topological_sort_dfs());
+    }
+
+    #[test]
+    fn kahn_breaks_ties_by_id() {
+        let mut dag = Dag::new();
+        for id in [9, 2, 7, 5, 1].iter() {
+            dag.add_node(*id).unwrap();
+        }
+        // 9 is ready first but has the children with the highest ids.
+        dag.add_edge(9, 7).unwrap();
+        dag.add_edge(9, 5).unwrap();
+        dag.add_edge(2, 1).unwrap();
+        assert_eq!(dag.topological_sort_kahn(), vec![2, 1, 9, 5, 7]);
+    }
+
+    #[test]
+    fn reachability() {
+        let dag = setup_dag();
+        assert_eq!(
+            dag.ancestors(3).into_iter().collect::<Vec<usize>>(),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            dag.descendants(1).into_iter().collect::<Vec<usize>>(),
+            vec![3, 4]
+        );
+        assert!(dag.descendants(4).is_empty());
+        assert_eq!(dag.parents(4), vec![3, 1]);
+    }
+
+    #[test]
+    fn transitive_reduction() {
+        let mut dag = setup_dag();
+        let reduced = dag.transitive_reduction();
+        assert_eq!(reduced.children(0), vec![1, 2]);
+        assert_eq!(reduced.children(1), vec![3]);
+        assert_eq!(reduced.children(2), vec![3]);
+        assert_eq!(reduced.children(3), vec![4]);
+        assert_eq!(reduced.descendants(0), dag.descendants(0));
+
+        assert!(dag.remove_edge(1, 4));
+        assert!(!dag.remove_edge(1, 4));
+        assert_eq!(dag.parents(4), vec![3]);
+    }
+}
@@ -0,0 +1,289 @@
+//! Union-find over the elements `0..n`.
+//!
+//! [`DisjointSet`] combines union by rank with path compression, giving
+//! near-constant amortised time per operation (Tarjan, 1975).
+//! [`RollbackDisjointSet`] drops path compression so that every union changes
+//! O(1) entries and can be undone, as needed for offline dynamic connectivity.
+//!
+//! Elements are plain indices; passing one that is out of range panics, just
+//! like indexing a slice.
+
+use std::collections::BTreeMap;
+
+/// Collects the elements of every set, ordered by their smallest member.
+fn collect_groups<F: FnMut(usize) -> usize>(len: usize, mut find: F) -> Vec<Vec<usize>> {
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut first_member: Vec<Option<usize>> = vec![None; len];
+    for x in 0..len {
+        let root = find(x);
+        let first = *first_member[root].get_or_insert(x);
+        groups.entry(first).or_default().push(x);
+    }
+    groups.into_values().collect()
+}
+
+pub struct DisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+    /// Set sizes, only kept up to date at roots.
+    size: Vec<usize>,
+    sets: usize,
+}
+
+impl DisjointSet {
+    /// `len` singleton sets.
+    pub fn new(len: usize) -> Self {
+        DisjointSet {
+            parent: (0..len).collect(),
+            rank: vec![0; len],
+            size: vec![1; len],
+            sets: len,
+        }
+    }
+
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// Adds a new singleton set and returns its element.
+    pub fn make_set(&mut self) -> usize {
+        let x = self.parent.len();
+        self.parent.push(x);
+        self.rank.push(0);
+        self.size.push(1);
+        self.sets += 1;
+        x
+    }
+
+    /// Representative of the set containing `x`.
+    pub fn find(&mut self, mut x: usize) -> usize {
+        // Path halving: point every other node on the path at its grandparent.
+        while self.parent[x] != x {
+            let grandparent = self.parent[self.parent[x]];
+            self.parent[x] = grandparent;
+            x = grandparent;
+        }
+        x
+    }
+
+    /// Merges the sets containing `a` and `b`, returning `false` if they were
+    /// already the same set.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.rank[a] < self.rank[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        if self.rank[a] == self.rank[b] {
+            self.rank[a] += 1;
+        }
+        self.sets -= 1;
+        true
+    }
+
+    pub fn same_set(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    /// Number of elements in the set containing `x`.
+    pub fn set_size(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        self.size[root]
+    }
+
+    pub fn num_sets(&self) -> usize {
+        self.sets
+    }
+
+    /// Members of every set in ascending order, with the sets ordered by their
+    /// smallest member.
+    pub fn groups(&mut self) -> impl Iterator<Item = Vec<usize>> {
+        collect_groups(self.len(), |x| self.find(x)).into_iter()
+    }
+}
+
+/// A union that can be undone: `child` was attached below `root`.
+struct Merge {
+    child: usize,
+    root: usize,
+    rank_increased: bool,
+}
+
+/// Union-find whose unions can be rolled back in LIFO order.
+///
+/// Without path compression `find` is O(log n), but it never modifies the
+/// structure, so queries only need `&self`.
+pub struct RollbackDisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+    size: Vec<usize>,
+    sets: usize,
+    history: Vec<Merge>,
+}
+
+impl RollbackDisjointSet {
+    /// `len` singleton sets.
+    pub fn new(len: usize) -> Self {
+        RollbackDisjointSet {
+            parent: (0..len).collect(),
+            rank: vec![0; len],
+            size: vec![1; len],
+            sets: len,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    pub fn find(&self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// Merges the sets containing `a` and `b`, returning `false` if they were
+    /// already the same set.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.rank[a] < self.rank[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        let rank_increased = self.rank[a] == self.rank[b];
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        if rank_increased {
+            self.rank[a] += 1;
+        }
+        self.sets -= 1;
+        self.history.push(Merge {
+            child: b,
+            root: a,
+            rank_increased,
+        });
+        true
+    }
+
+    pub fn same_set(&self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    pub fn set_size(&self, x: usize) -> usize {
+        self.size[self.find(x)]
+    }
+
+    pub fn num_sets(&self) -> usize {
+        self.sets
+    }
+
+    /// Marks the current state so that `rollback` can return to it.
+    pub fn snapshot(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Undoes every successful union made since `snapshot` was taken.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `snapshot` is newer than the current state, i.e. it was taken
+    /// and then already rolled past.
+    pub fn rollback(&mut self, snapshot: usize) {
+        assert!(
+            snapshot <= self.history.len(),
+            "snapshot {} has already been rolled back",
+            snapshot
+        );
+        while self.history.len() > snapshot {
+            let Merge {
+                child,
+                root,
+                rank_increased,
+            } = self.history.pop().unwrap();
+            self.parent[child] = child;
+            self.size[root] -= self.size[child];
+            if rank_increased {
+                self.rank[root] -= 1;
+            }
+            self.sets += 1;
+        }
+    }
+
+    /// Members of every set in ascending order, with the sets ordered by their
+    /// smallest member.
+    pub fn groups(&self) -> impl Iterator<Item = Vec<usize>> {
+        collect_groups(self.len(), |x| self.find(x)).into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn union_find() {
+        let mut sets = DisjointSet::new(8);
+        assert_eq!(sets.num_sets(), 8);
+        assert!(sets.union(0, 1));
+        assert!(sets.union(2, 3));
+        assert!(sets.union(1, 3));
+        assert!(!sets.union(0, 2));
+        assert!(sets.union(5, 6));
+
+        assert!(sets.same_set(0, 3));
+        assert!(!sets.same_set(3, 4));
+        assert_eq!(sets.set_size(2), 4);
+        assert_eq!(sets.num_sets(), 4);
+
+        let x = sets.make_set();
+        sets.union(x, 7);
+        let groups: Vec<Vec<usize>> = sets.groups().collect();
+        assert_eq!(
+            groups,
+            vec![vec![0, 1, 2, 3], vec![4], vec![5, 6], vec![7, 8]]
+        );
+    }
+
+    #[test]
+    fn rollback_restores_earlier_state() {
+        let mut sets = RollbackDisjointSet::new(6);
+        sets.union(0, 1);
+        let snapshot = sets.snapshot();
+        sets.union(2, 3);
+        sets.union(1, 3);
+        sets.union(4, 5);
+        assert!(sets.same_set(0, 2));
+        assert_eq!(sets.set_size(0), 4);
+        assert_eq!(sets.num_sets(), 2);
+
+        sets.rollback(snapshot);
+        assert!(sets.same_set(0, 1));
+        assert!(!sets.same_set(0, 2));
+        assert_eq!(sets.set_size(0), 2);
+        assert_eq!(sets.num_sets(), 5);
+        assert_eq!(
+            sets.groups().collect::<Vec<_>>(),
+            vec![vec![0, 1], vec![2], vec![3], vec![4], vec![5]]
+        );
+
+        sets.rollback(0);
+        assert_eq!(sets.num_sets(), 6);
+    }
+}
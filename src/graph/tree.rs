@@ -1,8 +1,60 @@
 //! Tree and tree-related algorithms.
 
+pub mod avl;
+pub mod bst;
+pub mod btree;
+pub mod fenwick;
+pub mod hld;
+pub mod interval;
+pub mod isomorphism;
+pub mod kd;
+pub mod lca;
+pub mod red_black;
+pub mod segment;
+pub mod serialize;
+pub mod splay;
+pub mod treap;
+pub mod trie;
+
+use snafu::Snafu;
 use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::ops::{Bound, RangeBounds};
 use std::rc::{Rc, Weak};
 
+#[derive(Debug, PartialEq, Snafu)]
+pub enum TreeError {
+    #[snafu(display("No node with id {} exists in the tree", id))]
+    NodeNotFound { id: usize },
+    #[snafu(display("A node with id {} already exists in the tree", id))]
+    DuplicateNode { id: usize },
+    #[snafu(display("Node {} is the root and cannot be removed", id))]
+    RootRemoval { id: usize },
+}
+
+/// Resolves `range` against a sequence of `len` elements, panicking like slice
+/// indexing when it is out of bounds.
+pub(crate) fn bounds<R: RangeBounds<usize>>(range: &R, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&s) => s,
+        Bound::Excluded(&s) => s + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&e) => e + 1,
+        Bound::Excluded(&e) => e,
+        Bound::Unbounded => len,
+    };
+    assert!(
+        start <= end && end <= len,
+        "range {}..{} is out of bounds for length {}",
+        start,
+        end,
+        len
+    );
+    (start, end)
+}
+
 /// A single tree node with parent and child references.
 pub struct Node {
     pub id: usize,
@@ -10,4 +62,328 @@ pub struct Node {
     pub parents: RefCell<Vec<Weak<Node>>>,
 }
 
-pub struct Tree {}
+impl Node {
+    pub fn new(id: usize) -> Rc<Node> {
+        Rc::new(Node {
+            id,
+            children: RefCell::new(Vec::new()),
+            parents: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// The first (and, within a `Tree`, only) parent of this node.
+    pub fn parent(&self) -> Option<Rc<Node>> {
+        self.parents
+            .borrow()
+            .first()
+            .and_then(|parent| parent.upgrade())
+    }
+}
+
+/// Rooted tree whose nodes are addressed by their unique ids.
+pub struct Tree {
+    root: Rc<Node>,
+    nodes: HashMap<usize, Rc<Node>>,
+}
+
+impl Tree {
+    pub fn new(root: usize) -> Self {
+        let node = Node::new(root);
+        let mut nodes = HashMap::new();
+        nodes.insert(root, node.clone());
+        Tree { root: node, nodes }
+    }
+
+    pub fn root(&self) -> usize {
+        self.root.id
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.nodes.contains_key(&id)
+    }
+
+    pub fn node(&self, id: usize) -> Option<&Rc<Node>> {
+        self.nodes.get(&id)
+    }
+
+    /// Attaches a new leaf `id` below `parent`.
+    pub fn add_child(&mut self, parent: usize, id: usize) -> Result<(), TreeError> {
+        let parent_node = self
+            .nodes
+            .get(&parent)
+            .ok_or(TreeError::NodeNotFound { id: parent })?;
+        if self.nodes.contains_key(&id) {
+            return Err(TreeError::DuplicateNode { id });
+        }
+        let child = Node::new(id);
+        child.parents.borrow_mut().push(Rc::downgrade(parent_node));
+        parent_node.children.borrow_mut().push(child.clone());
+        self.nodes.insert(id, child);
+        Ok(())
+    }
+
+    /// Parent of `id`, or `None` for the root and for unknown ids.
+    pub fn parent(&self, id: usize) -> Option<usize> {
+        self.nodes.get(&id)?.parent().map(|parent| parent.id)
+    }
+
+    /// Children of `id` in insertion order; empty for leaves and unknown ids.
+    pub fn children(&self, id: usize) -> Vec<usize> {
+        self.nodes.get(&id).map_or_else(Vec::new, |node| {
+            node.children
+                .borrow()
+                .iter()
+                .map(|child| child.id)
+                .collect()
+        })
+    }
+
+    /// Number of edges between `id` and the root.
+    pub fn depth(&self, id: usize) -> Option<usize> {
+        self.nodes.get(&id).map(|node| Ancestors::new(node).count())
+    }
+
+    /// Number of nodes in the subtree rooted at `id`, including `id` itself.
+    pub fn subtree_size(&self, id: usize) -> Option<usize> {
+        self.nodes.get(&id).map(|node| PreOrder::new(node).count())
+    }
+
+    /// Detaches `id` and all of its descendants, returning how many nodes were removed.
+    pub fn remove_subtree(&mut self, id: usize) -> Result<usize, TreeError> {
+        if id == self.root.id {
+            return Err(TreeError::RootRemoval { id });
+        }
+        let node = self
+            .nodes
+            .get(&id)
+            .ok_or(TreeError::NodeNotFound { id })?
+            .clone();
+        if let Some(parent) = node.parent() {
+            parent
+                .children
+                .borrow_mut()
+                .retain(|child| !Rc::ptr_eq(child, &node));
+        }
+
+        // As in `drop`, each node's children are still owned by `nodes` when its
+        // child list is cleared, so a deep subtree is not freed recursively.
+        let removed: Vec<usize> = PreOrder::new(&node).collect();
+        for id in removed.iter() {
+            if let Some(removed) = self.nodes.remove(id) {
+                removed.children.borrow_mut().clear();
+            }
+        }
+        Ok(removed.len())
+    }
+
+    /// Ids from the root downwards, visiting each node before its children.
+    pub fn pre_order(&self) -> PreOrder {
+        PreOrder::new(&self.root)
+    }
+
+    /// Ids from the leaves upwards, visiting each node after its children.
+    pub fn post_order(&self) -> PostOrder {
+        PostOrder {
+            stack: vec![(self.root.clone(), 0)],
+        }
+    }
+
+    /// Ids in breadth-first order, one depth level at a time.
+    pub fn level_order(&self) -> LevelOrder {
+        LevelOrder {
+            queue: vec![self.root.clone()].into_iter().collect(),
+        }
+    }
+
+    /// Ids of the parent of `id`, its parent, and so on up to the root.
+    pub fn ancestors(&self, id: usize) -> Ancestors {
+        match self.nodes.get(&id) {
+            Some(node) => Ancestors::new(node),
+            None => Ancestors { current: None },
+        }
+    }
+}
+
+impl Drop for Tree {
+    fn drop(&mut self) {
+        // Every node is also owned by `nodes`, so emptying the child lists first
+        // keeps deep trees from being dropped recursively.
+        for node in self.nodes.values() {
+            node.children.borrow_mut().clear();
+        }
+    }
+}
+
+pub struct PreOrder {
+    stack: Vec<Rc<Node>>,
+}
+
+impl PreOrder {
+    fn new(start: &Rc<Node>) -> Self {
+        PreOrder {
+            stack: vec![start.clone()],
+        }
+    }
+}
+
+impl Iterator for PreOrder {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let node = self.stack.pop()?;
+        self.stack
+            .extend(node.children.borrow().iter().rev().cloned());
+        Some(node.id)
+    }
+}
+
+pub struct PostOrder {
+    /// Nodes on the current path along with the index of their next unvisited child.
+    stack: Vec<(Rc<Node>, usize)>,
+}
+
+impl Iterator for PostOrder {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            let (node, next_child) = self.stack.last_mut()?;
+            let child = node.children.borrow().get(*next_child).cloned();
+            match child {
+                Some(child) => {
+                    *next_child += 1;
+                    self.stack.push((child, 0));
+                }
+                None => return self.stack.pop().map(|(node, _)| node.id),
+            }
+        }
+    }
+}
+
+pub struct LevelOrder {
+    queue: VecDeque<Rc<Node>>,
+}
+
+impl Iterator for LevelOrder {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.children.borrow().iter().cloned());
+        Some(node.id)
+    }
+}
+
+pub struct Ancestors {
+    current: Option<Rc<Node>>,
+}
+
+impl Ancestors {
+    fn new(node: &Rc<Node>) -> Self {
+        Ancestors {
+            current: node.parent(),
+        }
+    }
+}
+
+impl Iterator for Ancestors {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let node = self.current.take()?;
+        self.current = node.parent();
+        Some(node.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //        0
+    //      / | \
+    //     1  2  3
+    //    / \     \
+    //   4   5     6
+    //       |
+    //       7
+    fn setup_tree() -> Tree {
+        let mut tree = Tree::new(0);
+        for (parent, id) in [(0, 1), (0, 2), (0, 3), (1, 4), (1, 5), (3, 6), (5, 7)].iter() {
+            tree.add_child(*parent, *id).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn structure_queries() {
+        let tree = setup_tree();
+        assert_eq!(tree.size(), 8);
+        assert_eq!(tree.parent(7), Some(5));
+        assert_eq!(tree.parent(0), None);
+        assert_eq!(tree.children(1), vec![4, 5]);
+        assert_eq!(tree.depth(7), Some(3));
+        assert_eq!(tree.depth(0), Some(0));
+        assert_eq!(tree.subtree_size(1), Some(4));
+        assert_eq!(tree.ancestors(7).collect::<Vec<usize>>(), vec![5, 1, 0]);
+    }
+
+    #[test]
+    fn add_child_errors() {
+        let mut tree = setup_tree();
+        assert_eq!(
+            tree.add_child(42, 8),
+            Err(TreeError::NodeNotFound { id: 42 })
+        );
+        assert_eq!(
+            tree.add_child(2, 5),
+            Err(TreeError::DuplicateNode { id: 5 })
+        );
+    }
+
+    #[test]
+    fn traversals() {
+        let tree = setup_tree();
+        assert_eq!(
+            tree.pre_order().collect::<Vec<usize>>(),
+            vec![0, 1, 4, 5, 7, 2, 3, 6]
+        );
+        assert_eq!(
+            tree.post_order().collect::<Vec<usize>>(),
+            vec![4, 7, 5, 1, 2, 6, 3, 0]
+        );
+        assert_eq!(
+            tree.level_order().collect::<Vec<usize>>(),
+            vec![0, 1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn remove_subtree() {
+        let mut tree = setup_tree();
+        assert_eq!(
+            tree.remove_subtree(0),
+            Err(TreeError::RootRemoval { id: 0 })
+        );
+        assert_eq!(tree.remove_subtree(1), Ok(4));
+        assert_eq!(tree.size(), 4);
+        assert!(!tree.contains(7));
+        assert_eq!(tree.children(0), vec![2, 3]);
+        assert_eq!(tree.pre_order().collect::<Vec<usize>>(), vec![0, 2, 3, 6]);
+    }
+
+    #[test]
+    fn remove_deep_subtree_does_not_recurse() {
+        let mut tree = Tree::new(0);
+        for id in 1..200_000 {
+            tree.add_child(id - 1, id).unwrap();
+        }
+        assert_eq!(tree.remove_subtree(1), Ok(199_999));
+        assert_eq!(tree.size(), 1);
+        assert!(tree.children(0).is_empty());
+    }
+}
@@ -0,0 +1,431 @@
+//! AVL tree ordered map.
+//!
+//! A binary search tree in which the heights of the two subtrees of every
+//! node differ by at most one (Adelson-Velsky and Landis, 1962). Insertions
+//! and removals restore that balance with at most O(log n) rotations on the
+//! way back up, so lookups are O(log n) in the worst case.
+//!
+//! Nodes can carry an [`Augment`], a summary of their subtree that is
+//! recomputed whenever the subtree changes shape. The interval tree is built
+//! this way.
+
+use super::bst::{self, BinaryNode, InvariantError, Iter};
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::RangeBounds;
+
+type AvlLink<K, V, A> = Option<Box<AvlNode<K, V, A>>>;
+
+/// Data summarising a subtree, computed from a node's entry and the
+/// summaries of its children.
+pub trait Augment<K, V> {
+    fn combine(key: &K, value: &V, left: Option<&Self>, right: Option<&Self>) -> Self;
+}
+
+/// No augmentation.
+impl<K, V> Augment<K, V> for () {
+    fn combine(_: &K, _: &V, _: Option<&()>, _: Option<&()>) {}
+}
+
+pub struct AvlNode<K, V, A = ()> {
+    key: K,
+    value: V,
+    /// Number of nodes on the longest path down to a leaf, including this one.
+    height: usize,
+    augment: A,
+    left: AvlLink<K, V, A>,
+    right: AvlLink<K, V, A>,
+}
+
+impl<K, V, A> BinaryNode for AvlNode<K, V, A> {
+    type Key = K;
+    type Value = V;
+
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+fn height<K, V, A>(link: &AvlLink<K, V, A>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+impl<K, V, A> AvlNode<K, V, A> {
+    /// Summary of this node's subtree.
+    pub fn augment(&self) -> &A {
+        &self.augment
+    }
+
+    fn balance(&self) -> isize {
+        height(&self.left) as isize - height(&self.right) as isize
+    }
+}
+
+impl<K, V, A: Augment<K, V>> AvlNode<K, V, A> {
+    fn leaf(key: K, value: V) -> Box<Self> {
+        Box::new(AvlNode {
+            augment: A::combine(&key, &value, None, None),
+            key,
+            value,
+            height: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    /// Recomputes the height and augmentation from the children.
+    fn update(&mut self) {
+        self.height = 1 + height(&self.left).max(height(&self.right));
+        self.augment = A::combine(
+            &self.key,
+            &self.value,
+            self.left.as_ref().map(|left| &left.augment),
+            self.right.as_ref().map(|right| &right.augment),
+        );
+    }
+
+    fn rotate_right(mut self: Box<Self>) -> Box<Self> {
+        let mut left = self.left.take().expect("rotation needs a left child");
+        self.left = left.right.take();
+        self.update();
+        left.right = Some(self);
+        left.update();
+        left
+    }
+
+    fn rotate_left(mut self: Box<Self>) -> Box<Self> {
+        let mut right = self.right.take().expect("rotation needs a right child");
+        self.right = right.left.take();
+        self.update();
+        right.left = Some(self);
+        right.update();
+        right
+    }
+
+    /// Restores the AVL property at this node after one of its subtrees
+    /// changed height by at most one.
+    fn rebalance(mut self: Box<Self>) -> Box<Self> {
+        self.update();
+        match self.balance() {
+            2 => {
+                if self.left.as_ref().unwrap().balance() < 0 {
+                    self.left = self.left.take().map(|left| left.rotate_left());
+                }
+                self.rotate_right()
+            }
+            -2 => {
+                if self.right.as_ref().unwrap().balance() > 0 {
+                    self.right = self.right.take().map(|right| right.rotate_right());
+                }
+                self.rotate_left()
+            }
+            _ => self,
+        }
+    }
+
+    /// Splits off the leftmost node, returning the rebalanced remainder and that node.
+    fn remove_min(mut self: Box<Self>) -> (AvlLink<K, V, A>, Box<Self>) {
+        match self.left.take() {
+            None => (self.right.take(), self),
+            Some(left) => {
+                let (rest, min) = left.remove_min();
+                self.left = rest;
+                (Some(self.rebalance()), min)
+            }
+        }
+    }
+}
+
+/// Height-balanced binary search tree mapping ordered keys to values,
+/// optionally augmented with a summary `A` of every subtree.
+pub struct AvlMap<K, V, A = ()> {
+    root: AvlLink<K, V, A>,
+    size: usize,
+}
+
+impl<K: Ord, V> AvlMap<K, V> {
+    pub fn new() -> Self {
+        Self::augmented()
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> AvlMap<K, V, A> {
+    /// An empty map maintaining the augmentation `A`.
+    pub fn augmented() -> Self {
+        AvlMap {
+            root: None,
+            size: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn height(&self) -> usize {
+        height(&self.root)
+    }
+
+    /// Root node, from which augmented searches can descend.
+    pub fn root(&self) -> Option<&AvlNode<K, V, A>> {
+        self.root.as_deref()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        bst::search(self.root.as_deref(), key).map(|node| &node.value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (root, replaced) = Self::insert_at(self.root.take(), key, value);
+        self.root = Some(root);
+        if replaced.is_none() {
+            self.size += 1;
+        }
+        replaced
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (root, removed) = Self::remove_at(self.root.take(), key);
+        self.root = root;
+        if removed.is_some() {
+            self.size -= 1;
+        }
+        removed
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        bst::first(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        bst::last(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    /// Entry with the greatest key less than or equal to `key`.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        bst::floor(self.root.as_deref(), key).map(|node| (&node.key, &node.value))
+    }
+
+    /// Entry with the least key greater than or equal to `key`.
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        bst::ceiling(self.root.as_deref(), key).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn iter(&self) -> Iter<'_, AvlNode<K, V, A>> {
+        Iter::new(self.root.as_deref(), &(..))
+    }
+
+    /// Entries whose keys lie within `range`, in ascending key order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, AvlNode<K, V, A>> {
+        Iter::new(self.root.as_deref(), &range)
+    }
+
+    /// Checks that keys are ordered, that every stored height and
+    /// augmentation is correct and that no node's subtrees differ in height
+    /// by more than one.
+    pub fn validate(&self) -> Result<(), InvariantError>
+    where
+        A: PartialEq,
+    {
+        bst::validate_order(self.root.as_deref())?;
+        Self::validate_node(&self.root).map(|_| ())
+    }
+
+    fn validate_node(link: &AvlLink<K, V, A>) -> Result<usize, InvariantError>
+    where
+        A: PartialEq,
+    {
+        let node = match link {
+            Some(node) => node,
+            None => return Ok(0),
+        };
+        let left = Self::validate_node(&node.left)?;
+        let right = Self::validate_node(&node.right)?;
+        let expected = 1 + left.max(right);
+        if node.height != expected {
+            return Err(InvariantError::Height {
+                expected,
+                found: node.height,
+            });
+        }
+        if left.abs_diff(right) > 1 {
+            return Err(InvariantError::Unbalanced { left, right });
+        }
+        let augment = A::combine(
+            &node.key,
+            &node.value,
+            node.left.as_ref().map(|left| &left.augment),
+            node.right.as_ref().map(|right| &right.augment),
+        );
+        if node.augment != augment {
+            return Err(InvariantError::Augmentation);
+        }
+        Ok(expected)
+    }
+
+    fn insert_at(link: AvlLink<K, V, A>, key: K, value: V) -> (Box<AvlNode<K, V, A>>, Option<V>) {
+        let mut node = match link {
+            Some(node) => node,
+            None => return (AvlNode::leaf(key, value), None),
+        };
+        let replaced = match key.cmp(&node.key) {
+            Ordering::Equal => {
+                let replaced = mem::replace(&mut node.value, value);
+                node.update();
+                return (node, Some(replaced));
+            }
+            Ordering::Less => {
+                let (left, replaced) = Self::insert_at(node.left.take(), key, value);
+                node.left = Some(left);
+                replaced
+            }
+            Ordering::Greater => {
+                let (right, replaced) = Self::insert_at(node.right.take(), key, value);
+                node.right = Some(right);
+                replaced
+            }
+        };
+        (node.rebalance(), replaced)
+    }
+
+    fn remove_at(link: AvlLink<K, V, A>, key: &K) -> (AvlLink<K, V, A>, Option<V>) {
+        let mut node = match link {
+            Some(node) => node,
+            None => return (None, None),
+        };
+        match key.cmp(&node.key) {
+            Ordering::Less => {
+                let (left, removed) = Self::remove_at(node.left.take(), key);
+                node.left = left;
+                (Some(node.rebalance()), removed)
+            }
+            Ordering::Greater => {
+                let (right, removed) = Self::remove_at(node.right.take(), key);
+                node.right = right;
+                (Some(node.rebalance()), removed)
+            }
+            Ordering::Equal => {
+                let AvlNode {
+                    value, left, right, ..
+                } = *node;
+                let replacement = match (left, right) {
+                    (None, right) => right,
+                    (left, None) => left,
+                    (left, Some(right)) => {
+                        let (rest, mut successor) = right.remove_min();
+                        successor.left = left;
+                        successor.right = rest;
+                        Some(successor.rebalance())
+                    }
+                };
+                (replacement, Some(value))
+            }
+        }
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Default for AvlMap<K, V, A> {
+    fn default() -> Self {
+        Self::augmented()
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> FromIterator<(K, V)> for AvlMap<K, V, A> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = AvlMap::augmented();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<'a, K: Ord, V, A: Augment<K, V>> IntoIterator for &'a AvlMap<K, V, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, AvlNode<K, V, A>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::bst::BstMap;
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn sorted_inserts_stay_balanced() {
+        let map: AvlMap<u32, u32> = (0..1024).map(|x| (x, x * x)).collect();
+        assert_eq!(map.len(), 1024);
+        assert!(map.height() <= 11);
+        assert!(map.validate().is_ok());
+        assert_eq!(map.get(&31), Some(&961));
+        assert_eq!(map.first(), Some((&0, &0)));
+        assert_eq!(map.last(), Some((&1023, &(1023 * 1023))));
+    }
+
+    #[test]
+    fn matches_reference_bst() {
+        let mut rng = StdRng::seed_from_u64(34);
+        let mut avl: AvlMap<i32, i32> = AvlMap::new();
+        let mut reference: BstMap<i32, i32> = BstMap::new();
+
+        for step in 0..2000 {
+            let key = rng.gen_range(0..300);
+            if rng.gen_bool(0.6) {
+                assert_eq!(avl.insert(key, step), reference.insert(key, step));
+            } else {
+                assert_eq!(avl.remove(&key), reference.remove(&key));
+            }
+        }
+        assert!(avl.validate().is_ok());
+        assert_eq!(avl.len(), reference.len());
+        assert!(avl.iter().eq(reference.iter()));
+        assert!(avl.range(50..150).eq(reference.range(50..150)));
+        assert_eq!(avl.floor(&151), reference.floor(&151));
+        assert_eq!(avl.ceiling(&151), reference.ceiling(&151));
+    }
+
+    #[test]
+    fn validate_detects_imbalance() {
+        let mut map: AvlMap<i32, ()> = (0..3).map(|x| (x, ())).collect();
+        let root = map.root.as_mut().unwrap();
+        let mut right = root.right.take().unwrap();
+        right.right = Some(AvlNode::leaf(5, ()));
+        right.right.as_mut().unwrap().right = Some(AvlNode::leaf(6, ()));
+        right.right.as_mut().unwrap().update();
+        right.update();
+        root.right = Some(right);
+        root.update();
+
+        assert_eq!(
+            map.validate(),
+            Err(InvariantError::Unbalanced { left: 0, right: 2 })
+        );
+    }
+}
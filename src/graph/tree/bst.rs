@@ -0,0 +1,528 @@
+//! Binary search tree ordered map.
+//!
+//! `BstMap` is the plain, unbalanced binary search tree (CLRS ch. 12). Its
+//! operations take time proportional to the height of the tree, which is
+//! O(log n) for random insertions but O(n) for sorted ones. It doubles as a
+//! reference implementation to test the balanced trees against, and the
+//! read-only machinery here (`BinaryNode`, lookups and `Iter`) is shared by
+//! every tree-shaped map in this module.
+
+use snafu::Snafu;
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+
+/// Broken structural invariant reported by the `validate` methods of the trees.
+#[derive(Debug, PartialEq, Snafu)]
+pub enum InvariantError {
+    #[snafu(display("Keys are not in strictly ascending in-order sequence"))]
+    Unordered,
+    #[snafu(display(
+        "Stored height {} does not match the actual height {}",
+        found,
+        expected
+    ))]
+    Height { expected: usize, found: usize },
+    #[snafu(display("Subtree heights {} and {} differ by more than one", left, right))]
+    Unbalanced { left: usize, right: usize },
+    #[snafu(display("The root is red"))]
+    RedRoot,
+    #[snafu(display("A red node has a red child"))]
+    DoubleRed,
+    #[snafu(display("A red link leans right"))]
+    RightLeaningRed,
+    #[snafu(display("Subtree black heights {} and {} differ", left, right))]
+    BlackHeight { left: usize, right: usize },
+    #[snafu(display(
+        "Stored subtree size {} does not match the actual size {}",
+        found,
+        expected
+    ))]
+    Size { expected: usize, found: usize },
+    #[snafu(display("Node holds {} keys, outside the allowed {}..={}", found, min, max))]
+    Occupancy {
+        found: usize,
+        min: usize,
+        max: usize,
+    },
+    #[snafu(display(
+        "Node with {} keys has {} children instead of one more",
+        keys,
+        children
+    ))]
+    ChildCount { keys: usize, children: usize },
+    #[snafu(display("Leaves are not all at the same depth"))]
+    LeafDepth,
+    #[snafu(display("A node's augmented value does not summarise its subtree"))]
+    Augmentation,
+}
+
+/// Read-only view of a node in a binary search tree.
+pub trait BinaryNode {
+    type Key;
+    type Value;
+
+    fn key(&self) -> &Self::Key;
+    fn value(&self) -> &Self::Value;
+    fn left(&self) -> Option<&Self>;
+    fn right(&self) -> Option<&Self>;
+}
+
+pub(super) fn search<'a, N>(mut node: Option<&'a N>, key: &N::Key) -> Option<&'a N>
+where
+    N: BinaryNode,
+    N::Key: Ord,
+{
+    while let Some(n) = node {
+        node = match key.cmp(n.key()) {
+            Ordering::Less => n.left(),
+            Ordering::Greater => n.right(),
+            Ordering::Equal => return Some(n),
+        };
+    }
+    None
+}
+
+/// Checks that an in-order walk visits keys in strictly ascending order.
+pub(super) fn validate_order<N>(root: Option<&N>) -> Result<(), InvariantError>
+where
+    N: BinaryNode,
+    N::Key: Ord,
+{
+    let mut keys = Iter::new(root, &(..)).map(|(key, _)| key);
+    let mut previous = match keys.next() {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+    for key in keys {
+        if key <= previous {
+            return Err(InvariantError::Unordered);
+        }
+        previous = key;
+    }
+    Ok(())
+}
+
+pub(super) fn first<N: BinaryNode>(mut node: Option<&N>) -> Option<&N> {
+    while let Some(left) = node?.left() {
+        node = Some(left);
+    }
+    node
+}
+
+pub(super) fn last<N: BinaryNode>(mut node: Option<&N>) -> Option<&N> {
+    while let Some(right) = node?.right() {
+        node = Some(right);
+    }
+    node
+}
+
+/// Node with the greatest key less than or equal to `key`.
+pub(super) fn floor<'a, N>(mut node: Option<&'a N>, key: &N::Key) -> Option<&'a N>
+where
+    N: BinaryNode,
+    N::Key: Ord,
+{
+    let mut best = None;
+    while let Some(n) = node {
+        match key.cmp(n.key()) {
+            Ordering::Less => node = n.left(),
+            Ordering::Greater => {
+                best = Some(n);
+                node = n.right();
+            }
+            Ordering::Equal => return Some(n),
+        }
+    }
+    best
+}
+
+/// Node with the least key greater than or equal to `key`.
+pub(super) fn ceiling<'a, N>(mut node: Option<&'a N>, key: &N::Key) -> Option<&'a N>
+where
+    N: BinaryNode,
+    N::Key: Ord,
+{
+    let mut best = None;
+    while let Some(n) = node {
+        match key.cmp(n.key()) {
+            Ordering::Greater => node = n.right(),
+            Ordering::Less => {
+                best = Some(n);
+                node = n.left();
+            }
+            Ordering::Equal => return Some(n),
+        }
+    }
+    best
+}
+
+pub(super) fn below_start<K: Ord>(key: &K, start: Bound<&K>) -> bool {
+    match start {
+        Bound::Included(s) => key < s,
+        Bound::Excluded(s) => key <= s,
+        Bound::Unbounded => false,
+    }
+}
+
+pub(super) fn past_end<K: Ord>(key: &K, end: Bound<&K>) -> bool {
+    match end {
+        Bound::Included(e) => key > e,
+        Bound::Excluded(e) => key >= e,
+        Bound::Unbounded => false,
+    }
+}
+
+/// In-order iterator over the entries of a binary search tree.
+pub struct Iter<'a, N> {
+    /// Nodes whose own entry and right subtree are still to be visited.
+    stack: Vec<&'a N>,
+    /// First node beyond the end of the iterated range.
+    end: Option<&'a N>,
+}
+
+impl<'a, N> Iter<'a, N>
+where
+    N: BinaryNode,
+    N::Key: Ord,
+{
+    pub(super) fn new<R: RangeBounds<N::Key>>(root: Option<&'a N>, range: &R) -> Self {
+        let mut stack = Vec::new();
+        let mut node = root;
+        while let Some(n) = node {
+            if below_start(n.key(), range.start_bound()) {
+                node = n.right();
+            } else {
+                stack.push(n);
+                node = n.left();
+            }
+        }
+
+        let mut end = None;
+        let mut node = root;
+        while let Some(n) = node {
+            if past_end(n.key(), range.end_bound()) {
+                end = Some(n);
+                node = n.left();
+            } else {
+                node = n.right();
+            }
+        }
+
+        if end.is_some_and(|e| below_start(e.key(), range.start_bound())) {
+            stack.clear();
+        }
+        Iter { stack, end }
+    }
+}
+
+impl<'a, N> Iterator for Iter<'a, N>
+where
+    N: BinaryNode,
+{
+    type Item = (&'a N::Key, &'a N::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        if self.end.is_some_and(|end| std::ptr::eq(end, node)) {
+            self.stack.clear();
+            return None;
+        }
+        let mut next = node.right();
+        while let Some(n) = next {
+            self.stack.push(n);
+            next = n.left();
+        }
+        Some((node.key(), node.value()))
+    }
+}
+
+type BstLink<K, V> = Option<Box<BstNode<K, V>>>;
+
+pub struct BstNode<K, V> {
+    key: K,
+    value: V,
+    left: BstLink<K, V>,
+    right: BstLink<K, V>,
+}
+
+impl<K, V> BinaryNode for BstNode<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+/// Unbalanced binary search tree mapping ordered keys to values.
+pub struct BstMap<K, V> {
+    root: BstLink<K, V>,
+    size: usize,
+}
+
+impl<K: Ord, V> BstMap<K, V> {
+    pub fn new() -> Self {
+        BstMap {
+            root: None,
+            size: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Number of nodes on the longest root-to-leaf path.
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut level: Vec<&BstNode<K, V>> = self.root.as_deref().into_iter().collect();
+        while !level.is_empty() {
+            height += 1;
+            level = level
+                .into_iter()
+                .flat_map(|node| node.left().into_iter().chain(node.right()))
+                .collect();
+        }
+        height
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        search(self.root.as_deref(), key).map(|node| &node.value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        Self::find_link(&mut self.root, key)
+            .as_mut()
+            .map(|node| &mut node.value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let link = Self::find_link(&mut self.root, &key);
+        match link {
+            Some(node) => Some(mem::replace(&mut node.value, value)),
+            None => {
+                *link = Some(Box::new(BstNode {
+                    key,
+                    value,
+                    left: None,
+                    right: None,
+                }));
+                self.size += 1;
+                None
+            }
+        }
+    }
+
+    /// Removes `key`, replacing its node by its in-order successor if it has two children.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let link = Self::find_link(&mut self.root, key);
+        let mut node = link.take()?;
+        *link = match (node.left.take(), node.right.take()) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(left), Some(right)) => {
+                let mut right = Some(right);
+                let mut successor = Self::take_min(&mut right);
+                successor.left = Some(left);
+                successor.right = right;
+                Some(successor)
+            }
+        };
+        self.size -= 1;
+        Some(node.value)
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        first(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        last(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    /// Entry with the greatest key less than or equal to `key`.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        floor(self.root.as_deref(), key).map(|node| (&node.key, &node.value))
+    }
+
+    /// Entry with the least key greater than or equal to `key`.
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        ceiling(self.root.as_deref(), key).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn iter(&self) -> Iter<'_, BstNode<K, V>> {
+        Iter::new(self.root.as_deref(), &(..))
+    }
+
+    /// Entries whose keys lie within `range`, in ascending key order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, BstNode<K, V>> {
+        Iter::new(self.root.as_deref(), &range)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// The link holding `key`, or the empty link where it would be inserted.
+    fn find_link<'a>(mut link: &'a mut BstLink<K, V>, key: &K) -> &'a mut BstLink<K, V> {
+        loop {
+            let go_left = match link.as_ref() {
+                None => return link,
+                Some(node) => match key.cmp(&node.key) {
+                    Ordering::Equal => return link,
+                    Ordering::Less => true,
+                    Ordering::Greater => false,
+                },
+            };
+            let node = link.as_mut().unwrap();
+            link = if go_left {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+        }
+    }
+
+    /// Detaches the leftmost node below a non-empty `link`.
+    fn take_min(mut link: &mut BstLink<K, V>) -> Box<BstNode<K, V>> {
+        while link.as_ref().unwrap().left.is_some() {
+            link = &mut link.as_mut().unwrap().left;
+        }
+        let mut min = link.take().unwrap();
+        *link = min.right.take();
+        min
+    }
+}
+
+impl<K: Ord, V> Default for BstMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for BstMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = BstMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a BstMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, BstNode<K, V>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V> Drop for BstMap<K, V> {
+    fn drop(&mut self) {
+        // Degenerate trees are as deep as they are large, so unlink iteratively.
+        let mut stack: Vec<Box<BstNode<K, V>>> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn setup_map() -> BstMap<i32, char> {
+        vec![
+            (50, 'a'),
+            (30, 'b'),
+            (70, 'c'),
+            (20, 'd'),
+            (40, 'e'),
+            (60, 'f'),
+            (80, 'g'),
+            (35, 'h'),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let mut map = setup_map();
+        assert_eq!(map.len(), 8);
+        assert_eq!(map.get(&40), Some(&'e'));
+        assert_eq!(map.insert(40, 'z'), Some('e'));
+        assert_eq!(map.get(&40), Some(&'z'));
+
+        assert_eq!(map.remove(&30), Some('b'));
+        assert_eq!(map.remove(&50), Some('a'));
+        assert_eq!(map.remove(&99), None);
+        assert_eq!(map.len(), 6);
+        assert!(!map.contains_key(&30));
+        assert_eq!(
+            map.keys().cloned().collect::<Vec<i32>>(),
+            vec![20, 35, 40, 60, 70, 80]
+        );
+    }
+
+    #[test]
+    fn ordered_queries() {
+        let map = setup_map();
+        assert_eq!(map.first(), Some((&20, &'d')));
+        assert_eq!(map.last(), Some((&80, &'g')));
+        assert_eq!(map.floor(&45), Some((&40, &'e')));
+        assert_eq!(map.floor(&40), Some((&40, &'e')));
+        assert_eq!(map.floor(&10), None);
+        assert_eq!(map.ceiling(&45), Some((&50, &'a')));
+        assert_eq!(map.ceiling(&81), None);
+    }
+
+    #[test]
+    fn range_iteration() {
+        let map = setup_map();
+        let keys = |r: Vec<(&i32, &char)>| r.into_iter().map(|(k, _)| *k).collect::<Vec<i32>>();
+        assert_eq!(keys(map.range(35..60).collect()), vec![35, 40, 50]);
+        assert_eq!(keys(map.range(35..=60).collect()), vec![35, 40, 50, 60]);
+        assert_eq!(keys(map.range(..30).collect()), vec![20]);
+        assert_eq!(keys(map.range(71..).collect()), vec![80]);
+        assert_eq!(keys(map.range(41..49).collect()), Vec::<i32>::new());
+        assert_eq!(
+            keys(
+                map.range((Bound::Included(60), Bound::Excluded(40)))
+                    .collect()
+            ),
+            Vec::<i32>::new()
+        );
+        assert_eq!(map.iter().count(), 8);
+    }
+}
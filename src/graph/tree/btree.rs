@@ -0,0 +1,558 @@
+//! B-tree map with a configurable node order.
+//!
+//! Follows CLRS ch. 18, where `B` is the minimum degree: every node other than
+//! the root holds between `B - 1` and `2B - 1` keys, and internal nodes have
+//! one more child than keys. Insertion splits full nodes on the way down and
+//! removal tops up (by borrowing from or merging with a sibling) any node it is
+//! about to descend into, so neither ever has to walk back up the tree.
+
+use super::bst::{below_start, past_end, InvariantError};
+use snafu::Snafu;
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::RangeBounds;
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum BTreeError {
+    #[snafu(display(
+        "Bulk loading needs strictly ascending keys, but entry {} is out of order",
+        index
+    ))]
+    UnsortedInput { index: usize },
+}
+
+struct BNode<K, V> {
+    keys: Vec<K>,
+    values: Vec<V>,
+    /// Empty for leaves, otherwise one more than there are keys.
+    children: Vec<BNode<K, V>>,
+}
+
+impl<K, V> BNode<K, V> {
+    fn new() -> Self {
+        BNode {
+            keys: Vec::new(),
+            values: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+/// Ordered map stored in a B-tree of minimum degree `B`.
+pub struct BTreeMap<K, V, const B: usize = 6> {
+    root: BNode<K, V>,
+    len: usize,
+}
+
+impl<K: Ord, V, const B: usize> BTreeMap<K, V, B> {
+    const MAX_KEYS: usize = 2 * B - 1;
+
+    pub fn new() -> Self {
+        assert!(B >= 2, "B-trees need a minimum degree of at least 2");
+        BTreeMap {
+            root: BNode::new(),
+            len: 0,
+        }
+    }
+
+    /// Builds a tree bottom-up in O(n) from entries in strictly ascending key order.
+    pub fn bulk_load<I: IntoIterator<Item = (K, V)>>(entries: I) -> Result<Self, BTreeError> {
+        let mut tree = Self::new();
+        let mut keys: Vec<K> = Vec::new();
+        let mut values: Vec<V> = Vec::new();
+        for (index, (key, value)) in entries.into_iter().enumerate() {
+            if keys.last().is_some_and(|last| *last >= key) {
+                return Err(BTreeError::UnsortedInput { index });
+            }
+            keys.push(key);
+            values.push(value);
+        }
+        tree.len = keys.len();
+
+        let mut children: Vec<BNode<K, V>> = Vec::new();
+        while keys.len() > Self::MAX_KEYS {
+            // Split the level into groups separated by single entries, which
+            // become the keys of the level above.
+            let groups = (keys.len() + 1).div_ceil(2 * B);
+            let group_keys = keys.len() - (groups - 1);
+            let mut keys_iter = keys.into_iter();
+            let mut values_iter = values.into_iter();
+            let mut children_iter = children.into_iter();
+            let (mut level, mut up_keys, mut up_values) = (Vec::new(), Vec::new(), Vec::new());
+
+            for g in 0..groups {
+                let size = group_keys / groups + usize::from(g < group_keys % groups);
+                let node_children: Vec<BNode<K, V>> =
+                    children_iter.by_ref().take(size + 1).collect();
+                level.push(BNode {
+                    keys: keys_iter.by_ref().take(size).collect(),
+                    values: values_iter.by_ref().take(size).collect(),
+                    children: node_children,
+                });
+                if g + 1 < groups {
+                    up_keys.extend(keys_iter.next());
+                    up_values.extend(values_iter.next());
+                }
+            }
+            keys = up_keys;
+            values = up_values;
+            children = level;
+        }
+
+        tree.root = BNode {
+            keys,
+            values,
+            children,
+        };
+        Ok(tree)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of levels, counting the root and the leaves.
+    pub fn height(&self) -> usize {
+        let mut height = 1;
+        let mut node = &self.root;
+        while let Some(child) = node.children.first() {
+            height += 1;
+            node = child;
+        }
+        height
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let mut node = &self.root;
+        loop {
+            match node.keys.binary_search(key) {
+                Ok(i) => return Some(&node.values[i]),
+                Err(i) => node = node.children.get(i)?,
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        loop {
+            match node.keys.binary_search(key) {
+                Ok(i) => return Some(&mut node.values[i]),
+                Err(i) => node = node.children.get_mut(i)?,
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        let mut node = &self.root;
+        while let Some(child) = node.children.first() {
+            node = child;
+        }
+        node.keys.first().zip(node.values.first())
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        let mut node = &self.root;
+        while let Some(child) = node.children.last() {
+            node = child;
+        }
+        node.keys.last().zip(node.values.last())
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.root.keys.len() == Self::MAX_KEYS {
+            let old_root = mem::replace(&mut self.root, BNode::new());
+            self.root.children.push(old_root);
+            Self::split_child(&mut self.root, 0);
+        }
+        let replaced = Self::insert_non_full(&mut self.root, key, value);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let removed = Self::remove_from(&mut self.root, key);
+        if self.root.keys.is_empty() {
+            if let Some(child) = self.root.children.pop() {
+                self.root = child;
+            }
+        }
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    pub fn iter(&self) -> Range<'_, K, V> {
+        Range::new(&self.root, &(..))
+    }
+
+    /// Entries whose keys lie within `range`, in ascending key order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V> {
+        Range::new(&self.root, &range)
+    }
+
+    /// Checks key order, node occupancy, child counts and that all leaves are
+    /// on the same level.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        let mut keys = self.iter().map(|(key, _)| key);
+        if let Some(mut previous) = keys.next() {
+            for key in keys {
+                if key <= previous {
+                    return Err(InvariantError::Unordered);
+                }
+                previous = key;
+            }
+        }
+        Self::validate_node(&self.root, true).map(|_| ())
+    }
+
+    /// Returns the number of levels below and including `node`.
+    fn validate_node(node: &BNode<K, V>, is_root: bool) -> Result<usize, InvariantError> {
+        let min = if is_root { 0 } else { B - 1 };
+        if node.keys.len() < min || node.keys.len() > Self::MAX_KEYS {
+            return Err(InvariantError::Occupancy {
+                found: node.keys.len(),
+                min,
+                max: Self::MAX_KEYS,
+            });
+        }
+        if node.is_leaf() {
+            return Ok(1);
+        }
+        if node.children.len() != node.keys.len() + 1 {
+            return Err(InvariantError::ChildCount {
+                keys: node.keys.len(),
+                children: node.children.len(),
+            });
+        }
+        let mut depth = None;
+        for child in node.children.iter() {
+            let child_depth = Self::validate_node(child, false)?;
+            if depth.is_some_and(|d| d != child_depth) {
+                return Err(InvariantError::LeafDepth);
+            }
+            depth = Some(child_depth);
+        }
+        Ok(depth.unwrap() + 1)
+    }
+
+    /// Moves the median of the full child `i` up into `node`.
+    fn split_child(node: &mut BNode<K, V>, i: usize) {
+        let child = &mut node.children[i];
+        let right = BNode {
+            keys: child.keys.split_off(B),
+            values: child.values.split_off(B),
+            children: if child.is_leaf() {
+                Vec::new()
+            } else {
+                child.children.split_off(B)
+            },
+        };
+        let key = child.keys.pop().unwrap();
+        let value = child.values.pop().unwrap();
+        node.keys.insert(i, key);
+        node.values.insert(i, value);
+        node.children.insert(i + 1, right);
+    }
+
+    fn insert_non_full(node: &mut BNode<K, V>, key: K, value: V) -> Option<V> {
+        let mut i = match node.keys.binary_search(&key) {
+            Ok(i) => return Some(mem::replace(&mut node.values[i], value)),
+            Err(i) => i,
+        };
+        if node.is_leaf() {
+            node.keys.insert(i, key);
+            node.values.insert(i, value);
+            return None;
+        }
+        if node.children[i].keys.len() == Self::MAX_KEYS {
+            Self::split_child(node, i);
+            match key.cmp(&node.keys[i]) {
+                Ordering::Equal => return Some(mem::replace(&mut node.values[i], value)),
+                Ordering::Greater => i += 1,
+                Ordering::Less => {}
+            }
+        }
+        Self::insert_non_full(&mut node.children[i], key, value)
+    }
+
+    fn remove_from(node: &mut BNode<K, V>, key: &K) -> Option<V> {
+        match node.keys.binary_search(key) {
+            Ok(i) if node.is_leaf() => {
+                node.keys.remove(i);
+                Some(node.values.remove(i))
+            }
+            Ok(i) => {
+                if node.children[i].keys.len() >= B {
+                    let (k, v) = Self::remove_max(&mut node.children[i]);
+                    node.keys[i] = k;
+                    Some(mem::replace(&mut node.values[i], v))
+                } else if node.children[i + 1].keys.len() >= B {
+                    let (k, v) = Self::remove_min(&mut node.children[i + 1]);
+                    node.keys[i] = k;
+                    Some(mem::replace(&mut node.values[i], v))
+                } else {
+                    Self::merge_children(node, i);
+                    Self::remove_from(&mut node.children[i], key)
+                }
+            }
+            Err(_) if node.is_leaf() => None,
+            Err(i) => {
+                let i = Self::fill_child(node, i);
+                Self::remove_from(&mut node.children[i], key)
+            }
+        }
+    }
+
+    fn remove_max(node: &mut BNode<K, V>) -> (K, V) {
+        if node.is_leaf() {
+            return (node.keys.pop().unwrap(), node.values.pop().unwrap());
+        }
+        let i = Self::fill_child(node, node.children.len() - 1);
+        Self::remove_max(&mut node.children[i])
+    }
+
+    fn remove_min(node: &mut BNode<K, V>) -> (K, V) {
+        if node.is_leaf() {
+            return (node.keys.remove(0), node.values.remove(0));
+        }
+        let i = Self::fill_child(node, 0);
+        Self::remove_min(&mut node.children[i])
+    }
+
+    /// Makes sure child `i` has at least `B` keys before descending into it,
+    /// returning the index the child ends up at.
+    fn fill_child(node: &mut BNode<K, V>, i: usize) -> usize {
+        if node.children[i].keys.len() >= B {
+            i
+        } else if i > 0 && node.children[i - 1].keys.len() >= B {
+            Self::borrow_from_left(node, i);
+            i
+        } else if i + 1 < node.children.len() && node.children[i + 1].keys.len() >= B {
+            Self::borrow_from_right(node, i);
+            i
+        } else if i + 1 < node.children.len() {
+            Self::merge_children(node, i);
+            i
+        } else {
+            Self::merge_children(node, i - 1);
+            i - 1
+        }
+    }
+
+    fn borrow_from_left(node: &mut BNode<K, V>, i: usize) {
+        let (before, after) = node.children.split_at_mut(i);
+        let (left, child) = (&mut before[i - 1], &mut after[0]);
+        let key = mem::replace(&mut node.keys[i - 1], left.keys.pop().unwrap());
+        let value = mem::replace(&mut node.values[i - 1], left.values.pop().unwrap());
+        child.keys.insert(0, key);
+        child.values.insert(0, value);
+        if let Some(grandchild) = left.children.pop() {
+            child.children.insert(0, grandchild);
+        }
+    }
+
+    fn borrow_from_right(node: &mut BNode<K, V>, i: usize) {
+        let (before, after) = node.children.split_at_mut(i + 1);
+        let (child, right) = (&mut before[i], &mut after[0]);
+        let key = mem::replace(&mut node.keys[i], right.keys.remove(0));
+        let value = mem::replace(&mut node.values[i], right.values.remove(0));
+        child.keys.push(key);
+        child.values.push(value);
+        if !right.is_leaf() {
+            child.children.push(right.children.remove(0));
+        }
+    }
+
+    /// Merges child `i + 1` and the key separating it into child `i`.
+    fn merge_children(node: &mut BNode<K, V>, i: usize) {
+        let right = node.children.remove(i + 1);
+        let key = node.keys.remove(i);
+        let value = node.values.remove(i);
+        let left = &mut node.children[i];
+        left.keys.push(key);
+        left.values.push(value);
+        left.keys.extend(right.keys);
+        left.values.extend(right.values);
+        left.children.extend(right.children);
+    }
+}
+
+impl<K: Ord, V, const B: usize> Default for BTreeMap<K, V, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, K: Ord, V, const B: usize> IntoIterator for &'a BTreeMap<K, V, B> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Range<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// In-order iterator over the entries of a `BTreeMap`.
+pub struct Range<'a, K, V> {
+    /// Nodes on the current path with the index of the next key to yield.
+    stack: Vec<(&'a BNode<K, V>, usize)>,
+    /// Position of the first entry beyond the end of the range.
+    end: Option<(&'a BNode<K, V>, usize)>,
+}
+
+impl<'a, K: Ord, V> Range<'a, K, V> {
+    fn new<R: RangeBounds<K>>(root: &'a BNode<K, V>, range: &R) -> Self {
+        let mut stack = Vec::new();
+        let mut node = Some(root);
+        while let Some(n) = node {
+            let i = n
+                .keys
+                .partition_point(|key| below_start(key, range.start_bound()));
+            stack.push((n, i));
+            node = n.children.get(i);
+        }
+
+        let mut end = None;
+        let mut node = Some(root);
+        while let Some(n) = node {
+            let i = n
+                .keys
+                .partition_point(|key| !past_end(key, range.end_bound()));
+            if i < n.keys.len() {
+                end = Some((n, i));
+            }
+            node = n.children.get(i);
+        }
+        if end.is_some_and(|(n, i)| below_start(&n.keys[i], range.start_bound())) {
+            stack.clear();
+        }
+
+        Range { stack, end }
+    }
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, i) = self.stack.last_mut()?;
+            let (node, i) = (*node, *i);
+            if i >= node.keys.len() {
+                self.stack.pop();
+                continue;
+            }
+            if self
+                .end
+                .is_some_and(|(end, j)| std::ptr::eq(end, node) && j == i)
+            {
+                self.stack.clear();
+                return None;
+            }
+            self.stack.last_mut().unwrap().1 += 1;
+            let mut child = node.children.get(i + 1);
+            while let Some(c) = child {
+                self.stack.push((c, 0));
+                child = c.children.first();
+            }
+            return Some((&node.keys[i], &node.values[i]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::BTreeMap as StdBTreeMap;
+
+    #[test]
+    fn matches_std_btree() {
+        let mut rng = StdRng::seed_from_u64(36);
+        let mut tree: BTreeMap<i32, i32, 2> = BTreeMap::new();
+        let mut reference: StdBTreeMap<i32, i32> = StdBTreeMap::new();
+
+        for step in 0..4000 {
+            let key = rng.gen_range(0..500);
+            if rng.gen_bool(0.55) {
+                assert_eq!(tree.insert(key, step), reference.insert(key, step));
+            } else {
+                assert_eq!(tree.remove(&key), reference.remove(&key));
+            }
+            if step % 250 == 0 {
+                assert_eq!(tree.validate(), Ok(()));
+            }
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.len(), reference.len());
+        assert!(tree.iter().eq(reference.iter()));
+        assert!(tree.range(120..300).eq(reference.range(120..300)));
+        assert!(tree.range(..=77).eq(reference.range(..=77)));
+        assert_eq!(tree.first(), reference.iter().next());
+        assert_eq!(tree.last(), reference.iter().next_back());
+    }
+
+    #[test]
+    fn bulk_load_sorted_input() {
+        for n in [0, 1, 9, 10, 11, 100, 1000].iter() {
+            let tree: BTreeMap<u32, u32, 5> =
+                BTreeMap::bulk_load((0..*n).map(|x| (x, x * 2))).unwrap();
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(tree.len(), *n as usize);
+            assert!(tree.iter().map(|(k, _)| *k).eq(0..*n));
+        }
+
+        let mut tree: BTreeMap<u32, u32, 3> =
+            BTreeMap::bulk_load((0..500).map(|x| (x, x))).unwrap();
+        assert_eq!(tree.get(&321), Some(&321));
+        for x in (0..500).step_by(3) {
+            tree.remove(&x);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+
+        let unsorted = BTreeMap::<u32, (), 4>::bulk_load(vec![(1, ()), (3, ()), (3, ())]);
+        assert_eq!(unsorted.err(), Some(BTreeError::UnsortedInput { index: 2 }));
+    }
+
+    #[test]
+    fn node_order_controls_height() {
+        let narrow: BTreeMap<u32, (), 2> =
+            BTreeMap::bulk_load((0..10_000).map(|x| (x, ()))).unwrap();
+        let wide: BTreeMap<u32, (), 64> =
+            BTreeMap::bulk_load((0..10_000).map(|x| (x, ()))).unwrap();
+        assert!(wide.height() < narrow.height());
+        assert!(wide.height() <= 3);
+        assert_eq!(wide.range(9_990..).count(), 10);
+    }
+
+    #[test]
+    fn validate_detects_missing_child() {
+        let mut tree: BTreeMap<u32, (), 2> = BTreeMap::bulk_load((0..20).map(|x| (x, ()))).unwrap();
+        let children = tree.root.children.len();
+        tree.root.children.pop();
+        assert_eq!(
+            tree.validate(),
+            Err(InvariantError::ChildCount {
+                keys: children - 1,
+                children: children - 1
+            })
+        );
+    }
+}
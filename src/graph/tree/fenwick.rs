@@ -0,0 +1,372 @@
+//! Fenwick trees (binary indexed trees).
+//!
+//! Each slot `i` of the internal (1-based) array holds the sum of the
+//! `i & -i` elements ending at `i`, so both point updates and prefix sums touch
+//! O(log n) slots (Fenwick, 1994).
+
+use super::bounds;
+use num_traits::{Num, NumCast};
+use std::ops::RangeBounds;
+
+/// Lowest set bit of `i`.
+fn lowbit(i: usize) -> usize {
+    i & i.wrapping_neg()
+}
+
+/// Point updates and prefix sums over a sequence of numbers.
+pub struct FenwickTree<T> {
+    tree: Vec<T>,
+}
+
+impl<T: Num + Copy> FenwickTree<T> {
+    /// A tree of `len` zeros.
+    pub fn new(len: usize) -> Self {
+        FenwickTree {
+            tree: vec![T::zero(); len + 1],
+        }
+    }
+
+    /// Builds a tree over `values` in O(n).
+    pub fn from_vec(values: Vec<T>) -> Self {
+        let mut tree = vec![T::zero()];
+        tree.extend(values);
+        for i in 1..tree.len() {
+            let parent = i + lowbit(i);
+            if parent < tree.len() {
+                tree[parent] = tree[parent] + tree[i];
+            }
+        }
+        FenwickTree { tree }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds `delta` to the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn add(&mut self, index: usize, delta: T) {
+        assert!(
+            index < self.len(),
+            "index {} is out of bounds for length {}",
+            index,
+            self.len()
+        );
+        let mut i = index + 1;
+        while i < self.tree.len() {
+            self.tree[i] = self.tree[i] + delta;
+            i += lowbit(i);
+        }
+    }
+
+    /// Sum of the first `end` elements.
+    pub fn prefix_sum(&self, end: usize) -> T {
+        assert!(
+            end <= self.len(),
+            "prefix {} is out of bounds for length {}",
+            end,
+            self.len()
+        );
+        let mut sum = T::zero();
+        let mut i = end;
+        while i > 0 {
+            sum = sum + self.tree[i];
+            i -= lowbit(i);
+        }
+        sum
+    }
+
+    /// Sum of the elements within `range`.
+    pub fn range_sum<R: RangeBounds<usize>>(&self, range: R) -> T {
+        let (start, end) = bounds(&range, self.len());
+        self.prefix_sum(end) - self.prefix_sum(start)
+    }
+
+    pub fn get(&self, index: usize) -> T {
+        self.range_sum(index..=index)
+    }
+
+    /// Smallest index whose prefix sum (inclusive) reaches `target`, or `None`
+    /// if the total falls short. Only meaningful when no element is negative.
+    pub fn lower_bound(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        // Descend the implicit tree, skipping every block whose sum stays below target.
+        let mut position = 0;
+        let mut remaining = target;
+        let mut step = self.len().next_power_of_two();
+        while step > 0 {
+            let next = position + step;
+            if next < self.tree.len() && self.tree[next] < remaining {
+                position = next;
+                remaining = remaining - self.tree[next];
+            }
+            step /= 2;
+        }
+        if position < self.len() {
+            Some(position)
+        } else {
+            None
+        }
+    }
+}
+
+/// Fenwick tree supporting additions to whole ranges alongside range sums.
+///
+/// Conceptually keeps two trees `b1` and `b2` such that the prefix sum up to
+/// `i` is `b1(i) * i - b2(i)`. Each is split into the additions and the
+/// subtractions made to it, so that no intermediate value goes negative
+/// unless an element does, which keeps unsigned element types usable.
+pub struct RangeFenwickTree<T> {
+    b1_add: FenwickTree<T>,
+    b1_sub: FenwickTree<T>,
+    b2_add: FenwickTree<T>,
+    b2_sub: FenwickTree<T>,
+}
+
+impl<T: Num + NumCast + Copy> RangeFenwickTree<T> {
+    pub fn new(len: usize) -> Self {
+        RangeFenwickTree {
+            b1_add: FenwickTree::new(len + 1),
+            b1_sub: FenwickTree::new(len + 1),
+            b2_add: FenwickTree::new(len + 1),
+            b2_sub: FenwickTree::new(len + 1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.b1_add.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds `delta` to every element within `range`.
+    pub fn range_add<R: RangeBounds<usize>>(&mut self, range: R, delta: T) {
+        let (start, end) = bounds(&range, self.len());
+        if start == end {
+            return;
+        }
+        self.b1_add.add(start, delta);
+        self.b1_sub.add(end, delta);
+        self.b2_add.add(start, delta * Self::cast(start));
+        self.b2_sub.add(end, delta * Self::cast(end));
+    }
+
+    /// Sum of the first `end` elements.
+    pub fn prefix_sum(&self, end: usize) -> T {
+        assert!(
+            end <= self.len(),
+            "prefix {} is out of bounds for length {}",
+            end,
+            self.len()
+        );
+        // Every range ending before `end` also started before it, so the
+        // first difference is never negative, and the final result is the
+        // (non-negative) prefix sum itself.
+        let b1 = self.b1_add.prefix_sum(end) - self.b1_sub.prefix_sum(end);
+        b1 * Self::cast(end) + self.b2_sub.prefix_sum(end) - self.b2_add.prefix_sum(end)
+    }
+
+    /// Sum of the elements within `range`.
+    pub fn range_sum<R: RangeBounds<usize>>(&self, range: R) -> T {
+        let (start, end) = bounds(&range, self.len());
+        self.prefix_sum(end) - self.prefix_sum(start)
+    }
+
+    fn cast(index: usize) -> T {
+        T::from(index).expect("index does not fit the element type")
+    }
+}
+
+/// Point updates and rectangle sums over a grid of numbers.
+pub struct FenwickTree2D<T> {
+    rows: usize,
+    cols: usize,
+    /// Row-major `(rows + 1) x (cols + 1)` array, 1-based in both dimensions.
+    tree: Vec<T>,
+}
+
+impl<T: Num + Copy> FenwickTree2D<T> {
+    /// A `rows x cols` grid of zeros.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        FenwickTree2D {
+            rows,
+            cols,
+            tree: vec![T::zero(); (rows + 1) * (cols + 1)],
+        }
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    /// Adds `delta` to the cell at (`row`, `col`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell lies outside the grid.
+    pub fn add(&mut self, row: usize, col: usize, delta: T) {
+        assert!(
+            row < self.rows && col < self.cols,
+            "cell ({}, {}) is out of bounds for shape ({}, {})",
+            row,
+            col,
+            self.rows,
+            self.cols
+        );
+        let mut i = row + 1;
+        while i <= self.rows {
+            let mut j = col + 1;
+            while j <= self.cols {
+                let slot = i * (self.cols + 1) + j;
+                self.tree[slot] = self.tree[slot] + delta;
+                j += lowbit(j);
+            }
+            i += lowbit(i);
+        }
+    }
+
+    /// Sum of the cells in the first `rows` rows and `cols` columns.
+    pub fn prefix_sum(&self, rows: usize, cols: usize) -> T {
+        assert!(
+            rows <= self.rows && cols <= self.cols,
+            "prefix is out of bounds"
+        );
+        let mut sum = T::zero();
+        let mut i = rows;
+        while i > 0 {
+            let mut j = cols;
+            while j > 0 {
+                sum = sum + self.tree[i * (self.cols + 1) + j];
+                j -= lowbit(j);
+            }
+            i -= lowbit(i);
+        }
+        sum
+    }
+
+    /// Sum of the cells within the rectangle spanned by `rows` and `cols`.
+    pub fn rect_sum<R, C>(&self, rows: R, cols: C) -> T
+    where
+        R: RangeBounds<usize>,
+        C: RangeBounds<usize>,
+    {
+        let (top, bottom) = bounds(&rows, self.rows);
+        let (left, right) = bounds(&cols, self.cols);
+        self.prefix_sum(bottom, right) - self.prefix_sum(top, right) - self.prefix_sum(bottom, left)
+            + self.prefix_sum(top, left)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn prefix_sums_and_lower_bound() {
+        let mut tree = FenwickTree::from_vec(vec![3u32, 0, 4, 1, 5, 9, 2, 6]);
+        assert_eq!(tree.prefix_sum(0), 0);
+        assert_eq!(tree.prefix_sum(8), 30);
+        assert_eq!(tree.range_sum(2..5), 10);
+        tree.add(1, 2);
+        assert_eq!(tree.get(1), 2);
+        assert_eq!(tree.range_sum(..=2), 9);
+
+        // Cumulative: 3 5 9 10 15 24 26 32
+        assert_eq!(tree.lower_bound(1), Some(0));
+        assert_eq!(tree.lower_bound(5), Some(1));
+        assert_eq!(tree.lower_bound(6), Some(2));
+        assert_eq!(tree.lower_bound(25), Some(6));
+        assert_eq!(tree.lower_bound(32), Some(7));
+        assert_eq!(tree.lower_bound(33), None);
+    }
+
+    #[test]
+    fn range_updates_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(40);
+        let mut values = vec![0i64; 29];
+        let mut tree = RangeFenwickTree::new(values.len());
+
+        for _ in 0..500 {
+            let start = rng.gen_range(0..values.len());
+            let end = rng.gen_range(start..=values.len());
+            if rng.gen_bool(0.5) {
+                let delta = rng.gen_range(-20..20);
+                tree.range_add(start..end, delta);
+                values[start..end].iter_mut().for_each(|v| *v += delta);
+            } else {
+                assert_eq!(
+                    tree.range_sum(start..end),
+                    values[start..end].iter().sum::<i64>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn range_updates_on_unsigned_elements() {
+        let mut tree = RangeFenwickTree::<u32>::new(5);
+        tree.range_add(1..3, 2);
+        tree.range_add(2..5, 7);
+        assert_eq!(tree.range_sum(..), 25);
+        assert_eq!(tree.range_sum(2..3), 9);
+        assert_eq!(tree.prefix_sum(2), 2);
+
+        let mut rng = StdRng::seed_from_u64(41);
+        let mut values = [0u64; 17];
+        let mut tree = RangeFenwickTree::new(values.len());
+        for _ in 0..300 {
+            let start = rng.gen_range(0..values.len());
+            let end = rng.gen_range(start..=values.len());
+            let delta = rng.gen_range(0..1_000);
+            tree.range_add(start..end, delta);
+            values[start..end].iter_mut().for_each(|v| *v += delta);
+            let start = rng.gen_range(0..values.len());
+            let end = rng.gen_range(start..=values.len());
+            assert_eq!(
+                tree.range_sum(start..end),
+                values[start..end].iter().sum::<u64>()
+            );
+        }
+    }
+
+    #[test]
+    fn range_updates_on_float_elements() {
+        let mut tree = RangeFenwickTree::<f64>::new(6);
+        tree.range_add(0..4, 0.5);
+        tree.range_add(2..6, -1.25);
+        tree.range_add(.., 2.0);
+        assert_eq!(tree.range_sum(..), 2.0 - 5.0 + 12.0);
+        assert_eq!(tree.range_sum(2..4), 2.5);
+        assert_eq!(tree.prefix_sum(1), 2.5);
+    }
+
+    #[test]
+    fn rectangle_sums() {
+        let mut grid = FenwickTree2D::new(4, 5);
+        for row in 0..4 {
+            for col in 0..5 {
+                grid.add(row, col, (row * 5 + col) as f64);
+            }
+        }
+        assert_eq!(grid.prefix_sum(4, 5), 190.0);
+        assert_eq!(grid.rect_sum(1..3, 2..4), 7.0 + 8.0 + 12.0 + 13.0);
+        grid.add(2, 3, -13.0);
+        assert_eq!(grid.rect_sum(2..=2, ..), 10.0 + 11.0 + 12.0 + 14.0);
+    }
+}
@@ -0,0 +1,282 @@
+//! Heavy-light decomposition for path and subtree queries.
+//!
+//! Every node's heaviest child (the one with the largest subtree) continues
+//! its parent's chain, and all other children start chains of their own. A
+//! depth-first numbering that always visits the heavy child first lays every
+//! chain, and every subtree, out as a contiguous range. Any root-to-node path
+//! crosses O(log n) chains, so a path becomes O(log n) ranges of a
+//! [`LazySegmentTree`] and path operations take O(log² n).
+
+use super::segment::LazySegmentTree;
+use super::{Tree, TreeError};
+use crate::math::algebra::{Action, Monoid};
+use std::collections::HashMap;
+
+/// Node values of a `Tree`, arranged for path and subtree aggregates.
+///
+/// Path queries combine their ranges in no particular order, so the monoid
+/// should be commutative. The decomposition is a snapshot: later changes to
+/// the tree's shape are not reflected.
+pub struct HeavyLightDecomposition<M: Monoid, A: Action<M>> {
+    /// Node ids in pre-order, and the position of each id in that order.
+    ids: Vec<usize>,
+    index: HashMap<usize, usize>,
+    parent: Vec<usize>,
+    depth: Vec<usize>,
+    size: Vec<usize>,
+    /// Topmost node of the chain each node belongs to.
+    head: Vec<usize>,
+    /// Position of each node in the segment tree.
+    position: Vec<usize>,
+    segments: LazySegmentTree<M, A>,
+}
+
+impl<M: Monoid, A: Action<M>> HeavyLightDecomposition<M, A> {
+    /// Decomposes `tree` in O(n), giving each node the initial value `value(id)`.
+    pub fn new<F: FnMut(usize) -> M::Item>(
+        tree: &Tree,
+        monoid: M,
+        action: A,
+        mut value: F,
+    ) -> Self {
+        let ids: Vec<usize> = tree.pre_order().collect();
+        let n = ids.len();
+        let index: HashMap<usize, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let children: Vec<Vec<usize>> = ids
+            .iter()
+            .map(|id| {
+                tree.children(*id)
+                    .iter()
+                    .map(|child| index[child])
+                    .collect()
+            })
+            .collect();
+
+        let mut parent = vec![0; n];
+        let mut depth = vec![0; n];
+        for i in 0..n {
+            for child in children[i].iter() {
+                parent[*child] = i;
+                depth[*child] = depth[i] + 1;
+            }
+        }
+        // Pre-order puts every node before its descendants.
+        let mut size = vec![1; n];
+        for i in (1..n).rev() {
+            size[parent[i]] += size[i];
+        }
+
+        let mut head = vec![0; n];
+        let mut position = vec![0; n];
+        let mut stack = vec![0];
+        let mut next = 0;
+        while let Some(i) = stack.pop() {
+            position[i] = next;
+            next += 1;
+            let heavy = children[i].iter().copied().max_by_key(|child| size[*child]);
+            for child in children[i].iter().copied() {
+                if Some(child) != heavy {
+                    head[child] = child;
+                    stack.push(child);
+                }
+            }
+            if let Some(heavy) = heavy {
+                head[heavy] = head[i];
+                stack.push(heavy);
+            }
+        }
+
+        let mut values: Vec<Option<M::Item>> = vec![None; n];
+        for (i, id) in ids.iter().enumerate() {
+            values[position[i]] = Some(value(*id));
+        }
+        let values = values.into_iter().map(Option::unwrap).collect();
+
+        HeavyLightDecomposition {
+            ids,
+            index,
+            parent,
+            depth,
+            size,
+            head,
+            position,
+            segments: LazySegmentTree::from_vec(monoid, action, values),
+        }
+    }
+
+    pub fn get(&mut self, id: usize) -> Result<M::Item, TreeError> {
+        let i = self.node(id)?;
+        Ok(self.segments.get(self.position[i]).unwrap())
+    }
+
+    pub fn set(&mut self, id: usize, value: M::Item) -> Result<(), TreeError> {
+        let i = self.node(id)?;
+        self.segments.set(self.position[i], value);
+        Ok(())
+    }
+
+    /// Lowest common ancestor of `u` and `v`.
+    pub fn lca(&self, u: usize, v: usize) -> Result<usize, TreeError> {
+        let (mut u, mut v) = (self.node(u)?, self.node(v)?);
+        while self.head[u] != self.head[v] {
+            if self.depth[self.head[u]] < self.depth[self.head[v]] {
+                std::mem::swap(&mut u, &mut v);
+            }
+            u = self.parent[self.head[u]];
+        }
+        let lca = if self.depth[u] < self.depth[v] { u } else { v };
+        Ok(self.ids[lca])
+    }
+
+    /// Aggregate of the values on the path between `u` and `v`, both included.
+    pub fn path_query(&mut self, u: usize, v: usize) -> Result<M::Item, TreeError> {
+        let ranges = self.path_ranges(u, v)?;
+        let mut result = None;
+        for (start, end) in ranges {
+            let part = self.segments.query(start..end);
+            result = Some(match result {
+                Some(acc) => self.combine(&acc, &part),
+                None => part,
+            });
+        }
+        Ok(result.unwrap())
+    }
+
+    /// Applies `update` to every value on the path between `u` and `v`.
+    pub fn path_update(&mut self, u: usize, v: usize, update: A::Update) -> Result<(), TreeError> {
+        for (start, end) in self.path_ranges(u, v)? {
+            self.segments.update(start..end, update.clone());
+        }
+        Ok(())
+    }
+
+    /// Aggregate of the values in the subtree rooted at `id`.
+    pub fn subtree_query(&mut self, id: usize) -> Result<M::Item, TreeError> {
+        let i = self.node(id)?;
+        let start = self.position[i];
+        Ok(self.segments.query(start..start + self.size[i]))
+    }
+
+    /// Applies `update` to every value in the subtree rooted at `id`.
+    pub fn subtree_update(&mut self, id: usize, update: A::Update) -> Result<(), TreeError> {
+        let i = self.node(id)?;
+        let start = self.position[i];
+        self.segments.update(start..start + self.size[i], update);
+        Ok(())
+    }
+
+    fn node(&self, id: usize) -> Result<usize, TreeError> {
+        self.index
+            .get(&id)
+            .copied()
+            .ok_or(TreeError::NodeNotFound { id })
+    }
+
+    fn combine(&self, a: &M::Item, b: &M::Item) -> M::Item {
+        self.segments.monoid().combine(a, b)
+    }
+
+    /// Segment tree ranges covering the path between `u` and `v`.
+    fn path_ranges(&self, u: usize, v: usize) -> Result<Vec<(usize, usize)>, TreeError> {
+        let (mut u, mut v) = (self.node(u)?, self.node(v)?);
+        let mut ranges = Vec::new();
+        while self.head[u] != self.head[v] {
+            if self.depth[self.head[u]] < self.depth[self.head[v]] {
+                std::mem::swap(&mut u, &mut v);
+            }
+            ranges.push((self.position[self.head[u]], self.position[u] + 1));
+            u = self.parent[self.head[u]];
+        }
+        let (a, b) = (self.position[u], self.position[v]);
+        ranges.push((a.min(b), a.max(b) + 1));
+        Ok(ranges)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::tree::lca::LcaIndex;
+    use crate::math::algebra::{Add, Assign, Max, Sum};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_tree(rng: &mut StdRng, n: usize) -> Tree {
+        let mut tree = Tree::new(0);
+        for id in 1..n {
+            tree.add_child(rng.gen_range(0..id), id).unwrap();
+        }
+        tree
+    }
+
+    /// Nodes on the path between `u` and `v`, found by walking up from both.
+    fn path(tree: &Tree, u: usize, v: usize) -> Vec<usize> {
+        let mut up_u: Vec<usize> = Some(u).into_iter().chain(tree.ancestors(u)).collect();
+        let mut up_v: Vec<usize> = Some(v).into_iter().chain(tree.ancestors(v)).collect();
+        while up_u.len() > 1 && up_v.len() > 1 && up_u[up_u.len() - 2] == up_v[up_v.len() - 2] {
+            up_u.pop();
+            up_v.pop();
+        }
+        up_v.pop();
+        up_u.extend(up_v);
+        up_u
+    }
+
+    #[test]
+    fn path_sums_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(48);
+        let tree = random_tree(&mut rng, 200);
+        let mut values: Vec<i64> = (0..200).map(|_| rng.gen_range(-100..100)).collect();
+        let mut hld = HeavyLightDecomposition::new(&tree, Sum::new(), Add::new(), |id| values[id]);
+
+        for _ in 0..300 {
+            let (u, v) = (rng.gen_range(0..200), rng.gen_range(0..200));
+            let nodes = path(&tree, u, v);
+            if rng.gen_bool(0.4) {
+                let delta = rng.gen_range(-5..5);
+                hld.path_update(u, v, delta).unwrap();
+                nodes.iter().for_each(|id| values[*id] += delta);
+            } else {
+                assert_eq!(
+                    hld.path_query(u, v),
+                    Ok(nodes.iter().map(|id| values[*id]).sum())
+                );
+            }
+        }
+        let index = LcaIndex::new(&tree);
+        for _ in 0..100 {
+            let (u, v) = (rng.gen_range(0..200), rng.gen_range(0..200));
+            assert_eq!(hld.lca(u, v).ok(), index.lca(u, v));
+        }
+        assert_eq!(hld.subtree_query(0), Ok(values.iter().sum()));
+        assert_eq!(
+            hld.path_query(0, 999),
+            Err(TreeError::NodeNotFound { id: 999 })
+        );
+    }
+
+    #[test]
+    fn path_max_and_subtree_assign() {
+        //        1
+        //      /   \
+        //     2     3
+        //    / \     \
+        //   4   5     6
+        let mut tree = Tree::new(1);
+        for (parent, id) in [(1, 2), (1, 3), (2, 4), (2, 5), (3, 6)].iter() {
+            tree.add_child(*parent, *id).unwrap();
+        }
+        let mut hld =
+            HeavyLightDecomposition::new(&tree, Max::new(), Assign::new(), |id| id as i32 * 10);
+        assert_eq!(hld.path_query(4, 5), Ok(50));
+        assert_eq!(hld.path_query(4, 3), Ok(40));
+        assert_eq!(hld.lca(5, 6), Ok(1));
+
+        hld.subtree_update(2, Some(0)).unwrap();
+        assert_eq!(hld.path_query(4, 5), Ok(0));
+        assert_eq!(hld.path_query(5, 6), Ok(60));
+        hld.set(6, -1).unwrap();
+        assert_eq!(hld.get(6), Ok(-1));
+        assert_eq!(hld.subtree_query(1), Ok(30));
+    }
+}
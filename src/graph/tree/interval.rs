@@ -0,0 +1,294 @@
+//! Interval tree for overlap and stabbing queries.
+//!
+//! An AVL tree ordered by interval start (then end), where every node also
+//! records the largest end point in its subtree (CLRS ch. 14.3). A search can
+//! skip any subtree whose largest end lies at or before the query's start, so
+//! reporting the k intervals that overlap a query takes O(k log n).
+//!
+//! Intervals are half-open, like `Range`: `a..b` contains `a` but not `b`.
+
+use super::avl::{Augment, AvlMap, AvlNode};
+use super::bst::{BinaryNode, InvariantError};
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+use std::ops::{Bound, Range};
+
+/// Interval key ordered by start and then end.
+#[derive(PartialEq, Eq)]
+struct Interval<T>(Range<T>);
+
+impl<T: Ord> PartialOrd for Interval<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Interval<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.0.start, &self.0.end).cmp(&(&other.0.start, &other.0.end))
+    }
+}
+
+/// Largest end point of any interval in a subtree.
+#[derive(PartialEq)]
+struct MaxEnd<T>(T);
+
+impl<T: Ord + Clone, V> Augment<Interval<T>, V> for MaxEnd<T> {
+    fn combine(key: &Interval<T>, _: &V, left: Option<&Self>, right: Option<&Self>) -> Self {
+        let max_end = left
+            .into_iter()
+            .chain(right)
+            .map(|child| &child.0)
+            .fold(&key.0.end, |a, b| a.max(b));
+        MaxEnd(max_end.clone())
+    }
+}
+
+type IntervalNode<T, V> = AvlNode<Interval<T>, V, MaxEnd<T>>;
+
+/// Map from half-open intervals to values, answering overlap queries.
+pub struct IntervalTree<T, V> {
+    map: AvlMap<Interval<T>, V, MaxEnd<T>>,
+}
+
+impl<T: Ord + Clone, V> IntervalTree<T, V> {
+    pub fn new() -> Self {
+        IntervalTree {
+            map: AvlMap::augmented(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Value stored for exactly `interval`.
+    pub fn get(&self, interval: &Range<T>) -> Option<&V> {
+        self.map.get(&Interval(interval.clone()))
+    }
+
+    /// Inserts `value` under `interval`, returning the value previously stored
+    /// for that exact interval.
+    ///
+    /// An empty (or inverted) `interval` contains no points, so it is not
+    /// stored: `value` is dropped and `None` is returned.
+    pub fn insert(&mut self, interval: Range<T>, value: V) -> Option<V> {
+        if interval.start >= interval.end {
+            return None;
+        }
+        self.map.insert(Interval(interval), value)
+    }
+
+    pub fn remove(&mut self, interval: &Range<T>) -> Option<V> {
+        self.map.remove(&Interval(interval.clone()))
+    }
+
+    /// All intervals, ordered by start and then end.
+    pub fn iter(&self) -> Overlapping<'_, T, V> {
+        Overlapping::new(self.map.root(), None, Bound::Unbounded)
+    }
+
+    /// Intervals sharing at least one point with `range`, ordered by start.
+    /// An empty (or inverted) `range` overlaps nothing.
+    pub fn overlapping(&self, range: Range<T>) -> Overlapping<'_, T, V> {
+        let root = if range.start < range.end {
+            self.map.root()
+        } else {
+            None
+        };
+        Overlapping::new(root, Some(range.start), Bound::Excluded(range.end))
+    }
+
+    /// Intervals containing `point`, ordered by start.
+    pub fn stabbing(&self, point: &T) -> Overlapping<'_, T, V> {
+        Overlapping::new(
+            self.map.root(),
+            Some(point.clone()),
+            Bound::Included(point.clone()),
+        )
+    }
+
+    /// Checks ordering, AVL balance and every node's recorded largest end point.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        self.map.validate()
+    }
+}
+
+impl<T: Ord + Clone, V> Default for IntervalTree<T, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Clone, V> FromIterator<(Range<T>, V)> for IntervalTree<T, V> {
+    fn from_iter<I: IntoIterator<Item = (Range<T>, V)>>(iter: I) -> Self {
+        let mut tree = IntervalTree::new();
+        for (interval, value) in iter {
+            tree.insert(interval, value);
+        }
+        tree
+    }
+}
+
+/// In-order iterator over the intervals meeting a query, skipping subtrees
+/// that cannot contain a match.
+pub struct Overlapping<'a, T, V> {
+    stack: Vec<&'a IntervalNode<T, V>>,
+    /// Matching intervals must end after this point.
+    after: Option<T>,
+    /// Matching intervals must start within this bound.
+    before: Bound<T>,
+}
+
+impl<'a, T: Ord, V> Overlapping<'a, T, V> {
+    fn new(root: Option<&'a IntervalNode<T, V>>, after: Option<T>, before: Bound<T>) -> Self {
+        let mut iter = Overlapping {
+            stack: Vec::new(),
+            after,
+            before,
+        };
+        iter.push_left(root);
+        iter
+    }
+
+    fn ends_after(&self, end: &T) -> bool {
+        self.after.as_ref().is_none_or(|after| end > after)
+    }
+
+    fn starts_before(&self, start: &T) -> bool {
+        match &self.before {
+            Bound::Included(point) => start <= point,
+            Bound::Excluded(end) => start < end,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn push_left(&mut self, mut link: Option<&'a IntervalNode<T, V>>) {
+        while let Some(node) = link {
+            if !self.ends_after(&node.augment().0) {
+                break;
+            }
+            self.stack.push(node);
+            link = node.left();
+        }
+    }
+}
+
+impl<'a, T: Ord, V> Iterator for Overlapping<'a, T, V> {
+    type Item = (&'a Range<T>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let interval = &node.key().0;
+            if !self.starts_before(&interval.start) {
+                // Everything later in order starts at least as late.
+                self.stack.clear();
+                return None;
+            }
+            self.push_left(node.right());
+            if self.ends_after(&interval.end) {
+                return Some((interval, node.value()));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn overlap_and_stabbing() {
+        let tree: IntervalTree<u32, &str> = vec![
+            (9..12, "standup"),
+            (13..14, "lunch"),
+            (10..16, "workshop"),
+            (16..17, "retro"),
+            (8..9, "gym"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(tree.validate(), Ok(()));
+
+        let names = |iter: Overlapping<'_, u32, &'static str>| {
+            iter.map(|(_, name)| *name).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(tree.overlapping(11..14)),
+            vec!["standup", "workshop", "lunch"]
+        );
+        assert_eq!(names(tree.overlapping(16..20)), vec!["retro"]);
+        assert_eq!(names(tree.overlapping(0..8)), Vec::<&str>::new());
+        assert_eq!(names(tree.stabbing(&9)), vec!["standup"]);
+        assert_eq!(names(tree.stabbing(&12)), vec!["workshop"]);
+        assert_eq!(names(tree.stabbing(&17)), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn empty_intervals_overlap_nothing() {
+        let mut tree: IntervalTree<u32, ()> = vec![(0..10, ())].into_iter().collect();
+        assert_eq!(tree.overlapping(5..5).count(), 0);
+        #[allow(clippy::reversed_empty_ranges)]
+        let inverted = 7..3;
+        assert_eq!(tree.overlapping(inverted.clone()).count(), 0);
+        assert_eq!(tree.overlapping(5..6).count(), 1);
+
+        assert_eq!(tree.insert(4..4, ()), None);
+        assert_eq!(tree.insert(inverted, ()), None);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.stabbing(&4).count(), 1);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(45);
+        let mut tree = IntervalTree::new();
+        let mut reference: Vec<(Range<i32>, i32)> = Vec::new();
+
+        for step in 0..1500 {
+            let start = rng.gen_range(0..200);
+            let interval = start..start + rng.gen_range(1..30);
+            if rng.gen_bool(0.6) {
+                let expected = reference
+                    .iter()
+                    .position(|(r, _)| *r == interval)
+                    .map(|i| reference.remove(i).1);
+                reference.push((interval.clone(), step));
+                assert_eq!(tree.insert(interval, step), expected);
+            } else {
+                let expected = reference
+                    .iter()
+                    .position(|(r, _)| *r == interval)
+                    .map(|i| reference.remove(i).1);
+                assert_eq!(tree.remove(&interval), expected);
+            }
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.len(), reference.len());
+        reference.sort_by_key(|(r, _)| (r.start, r.end));
+
+        for _ in 0..100 {
+            let start = rng.gen_range(0..220);
+            let query = start..start + rng.gen_range(1..20);
+            let expected: Vec<&(Range<i32>, i32)> = reference
+                .iter()
+                .filter(|(r, _)| r.start < query.end && query.start < r.end)
+                .collect();
+            let found: Vec<(&Range<i32>, &i32)> = tree.overlapping(query).collect();
+            assert!(found
+                .into_iter()
+                .eq(expected.into_iter().map(|(r, v)| (r, v))));
+
+            let point = rng.gen_range(0..220);
+            let expected = reference.iter().filter(|(r, _)| r.contains(&point)).count();
+            assert_eq!(tree.stabbing(&point).count(), expected);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Tree isomorphism through canonical encodings.
+//!
+//! Following Aho, Hopcroft and Ullman, every subtree is encoded as the
+//! concatenation of its children's encodings in a canonical order, wrapped in
+//! a pair of parentheses. Two rooted trees are isomorphic exactly when their
+//! root encodings are equal. An unrooted tree is encoded from its center (or
+//! the smaller encoding of its two centers), which every isomorphic copy
+//! shares.
+
+use super::Tree;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Encoding of a tree's shape that is identical for exactly the isomorphic
+/// trees, so it can be hashed or compared to deduplicate them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CanonicalForm(Vec<u8>);
+
+impl fmt::Display for CanonicalForm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.0))
+    }
+}
+
+/// Encodes the tree hanging from `root`, where `neighbours(id)` lists the
+/// children of `id` and possibly its parent, which is skipped.
+///
+/// Rather than building every subtree's string, subtrees are interned level
+/// by level, from the leaves up, as integer classes: two nodes of the same
+/// height share a class exactly when their children's sorted classes agree,
+/// and the classes of each height are numbered in the order of those sorted
+/// tuples. Isomorphic trees therefore number their classes identically. The
+/// string is written once at the end, visiting children by descending class.
+fn encode<F: Fn(usize) -> Vec<usize>>(root: usize, neighbours: F) -> Vec<u8> {
+    // Children of each node, indexed in pre-order.
+    let mut children: Vec<Vec<usize>> = Vec::new();
+    // (node, its parent's id and local index)
+    let mut stack: Vec<(usize, Option<(usize, usize)>)> = vec![(root, None)];
+    while let Some((id, parent)) = stack.pop() {
+        let index = children.len();
+        children.push(Vec::new());
+        if let Some((_, parent)) = parent {
+            children[parent].push(index);
+        }
+        for neighbour in neighbours(id) {
+            if Some(neighbour) != parent.map(|(parent, _)| parent) {
+                stack.push((neighbour, Some((id, index))));
+            }
+        }
+    }
+
+    let n = children.len();
+    let mut height = vec![0; n];
+    for i in (0..n).rev() {
+        height[i] = children[i]
+            .iter()
+            .map(|c| height[*c] + 1)
+            .max()
+            .unwrap_or(0);
+    }
+    let mut levels: Vec<Vec<usize>> = vec![Vec::new(); height[0] + 1];
+    for i in 0..n {
+        levels[height[i]].push(i);
+    }
+
+    let mut class = vec![0; n];
+    let mut next_class = 0;
+    for level in levels {
+        let mut tuples: Vec<(Vec<usize>, usize)> = level
+            .into_iter()
+            .map(|i| {
+                let mut tuple: Vec<usize> = children[i].iter().map(|c| class[*c]).collect();
+                tuple.sort_unstable_by(|a, b| b.cmp(a));
+                (tuple, i)
+            })
+            .collect();
+        tuples.sort_unstable();
+        for (k, (tuple, i)) in tuples.iter().enumerate() {
+            if k > 0 && *tuple != tuples[k - 1].0 {
+                next_class += 1;
+            }
+            class[*i] = next_class;
+        }
+        next_class += 1;
+    }
+
+    let mut encoding = Vec::with_capacity(2 * n);
+    // (node, children already pushed)
+    let mut stack = vec![(0, false)];
+    while let Some((i, expanded)) = stack.pop() {
+        if expanded {
+            encoding.push(b')');
+            continue;
+        }
+        encoding.push(b'(');
+        stack.push((i, true));
+        let mut next: Vec<usize> = children[i].clone();
+        next.sort_unstable_by_key(|c| class[*c]);
+        stack.extend(next.into_iter().map(|c| (c, false)));
+    }
+    encoding
+}
+
+impl Tree {
+    /// Canonical form of the tree as rooted at its root.
+    pub fn canonical_form(&self) -> CanonicalForm {
+        CanonicalForm(encode(self.root(), |id| self.children(id)))
+    }
+
+    /// Canonical form of the tree with its root forgotten, so that trees
+    /// which only differ in where they are rooted share it.
+    pub fn unrooted_canonical_form(&self) -> CanonicalForm {
+        let neighbours = |id: usize| {
+            let mut neighbours = self.children(id);
+            neighbours.extend(self.parent(id));
+            neighbours
+        };
+        self.centers()
+            .into_iter()
+            .map(|center| CanonicalForm(encode(center, neighbours)))
+            .min()
+            .unwrap()
+    }
+
+    pub fn is_isomorphic(&self, other: &Tree) -> bool {
+        self.size() == other.size() && self.canonical_form() == other.canonical_form()
+    }
+
+    pub fn is_unrooted_isomorphic(&self, other: &Tree) -> bool {
+        self.size() == other.size()
+            && self.unrooted_canonical_form() == other.unrooted_canonical_form()
+    }
+
+    /// The one or two nodes minimising the longest path to any other node,
+    /// found by repeatedly stripping all leaves.
+    fn centers(&self) -> Vec<usize> {
+        let mut degree: HashMap<usize, usize> = self
+            .pre_order()
+            .map(|id| {
+                (
+                    id,
+                    self.children(id).len() + usize::from(self.parent(id).is_some()),
+                )
+            })
+            .collect();
+        let mut leaves: Vec<usize> = degree
+            .iter()
+            .filter(|(_, d)| **d <= 1)
+            .map(|(id, _)| *id)
+            .collect();
+        let mut remaining = degree.len();
+        while remaining > 2 {
+            remaining -= leaves.len();
+            let mut next = Vec::new();
+            for leaf in leaves {
+                for neighbour in self.children(leaf).into_iter().chain(self.parent(leaf)) {
+                    let d = degree.get_mut(&neighbour).unwrap();
+                    *d -= 1;
+                    if *d == 1 {
+                        next.push(neighbour);
+                    }
+                }
+            }
+            leaves = next;
+        }
+        leaves.sort_unstable();
+        leaves
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn build(root: usize, edges: &[(usize, usize)]) -> Tree {
+        let mut tree = Tree::new(root);
+        for (parent, id) in edges.iter() {
+            tree.add_child(*parent, *id).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn rooted_isomorphism() {
+        //     0            10
+        //    / \          /  \
+        //   1   2       11    12
+        //   |          /  \
+        //   3         13   14    <- not isomorphic: 1 has a single child
+        let a = build(0, &[(0, 1), (0, 2), (1, 3)]);
+        let b = build(10, &[(10, 12), (10, 11), (12, 13)]);
+        let c = build(10, &[(10, 11), (10, 12), (11, 13), (11, 14)]);
+        assert!(a.is_isomorphic(&b));
+        assert!(!a.is_isomorphic(&c));
+        assert_eq!(a.canonical_form().to_string(), "((())())");
+
+        let forms: HashSet<CanonicalForm> = vec![&a, &b, &c]
+            .into_iter()
+            .map(Tree::canonical_form)
+            .collect();
+        assert_eq!(forms.len(), 2);
+    }
+
+    #[test]
+    fn unrooted_isomorphism() {
+        // The same path 0-1-2-3 rooted at an end and in the middle.
+        let end = build(0, &[(0, 1), (1, 2), (2, 3)]);
+        let middle = build(2, &[(2, 1), (2, 3), (1, 0)]);
+        assert!(!end.is_isomorphic(&middle));
+        assert!(end.is_unrooted_isomorphic(&middle));
+
+        // A star is not a path.
+        let star = build(0, &[(0, 1), (0, 2), (0, 3)]);
+        assert!(!star.is_unrooted_isomorphic(&end));
+        let leaf_rooted_star = build(1, &[(1, 0), (0, 2), (0, 3)]);
+        assert!(star.is_unrooted_isomorphic(&leaf_rooted_star));
+        assert_eq!(
+            Tree::new(5).unrooted_canonical_form(),
+            Tree::new(9).canonical_form()
+        );
+    }
+
+    #[test]
+    fn deep_trees_do_not_recurse() {
+        let mut a = Tree::new(0);
+        let mut b = Tree::new(0);
+        for id in 1..100_000 {
+            a.add_child(id - 1, id).unwrap();
+            b.add_child(id - 1, id).unwrap();
+        }
+        assert!(a.is_isomorphic(&b));
+    }
+}
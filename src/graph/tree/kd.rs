@@ -0,0 +1,317 @@
+//! k-d tree for nearest-neighbour search.
+//!
+//! Points are split at the median along one coordinate per level, cycling
+//! through the axes (Bentley, 1975). The tree is stored implicitly: each
+//! subtree is a slice of the point array with its splitting point in the
+//! middle. Searches skip any subtree that lies entirely on the far side of a
+//! splitting plane further away than the best candidates found so far.
+
+use crate::math::linalg::matrix::Matrix;
+use snafu::Snafu;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum KdTreeError {
+    #[snafu(display("Expected an n x {} matrix but found shape {:?}", dimensions, shape))]
+    ShapeMismatch {
+        shape: Vec<usize>,
+        dimensions: usize,
+    },
+}
+
+/// Distance function used by the searches.
+pub trait Metric {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64;
+
+    /// Lower bound on the distance between two points whose coordinates
+    /// differ by `delta` along a single axis.
+    fn axis_distance(&self, delta: f64) -> f64 {
+        delta.abs()
+    }
+}
+
+/// Straight-line (L2) distance.
+pub struct Euclidean;
+
+/// Sum of coordinate differences (L1).
+pub struct Manhattan;
+
+/// Largest coordinate difference (L∞).
+pub struct Chebyshev;
+
+impl Metric for Euclidean {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y) * (x - y))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+impl Metric for Manhattan {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum()
+    }
+}
+
+impl Metric for Chebyshev {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f64::max)
+    }
+}
+
+/// A point found by a search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbour {
+    /// Position of the point in the input the tree was built from.
+    pub index: usize,
+    pub distance: f64,
+}
+
+impl Eq for Neighbour {}
+
+impl PartialOrd for Neighbour {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbour {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+/// Static k-d tree over points in `K` dimensions.
+pub struct KdTree<const K: usize> {
+    /// Points with their input positions, laid out as an implicit tree.
+    nodes: Vec<([f64; K], usize)>,
+    /// Points in input order.
+    points: Vec<[f64; K]>,
+}
+
+impl<const K: usize> KdTree<K> {
+    /// Builds a balanced tree in O(n log n).
+    pub fn new(points: Vec<[f64; K]>) -> Self {
+        assert!(K > 0, "points need at least one dimension");
+        let mut nodes: Vec<([f64; K], usize)> = points.iter().cloned().zip(0..).collect();
+        Self::build(&mut nodes, 0);
+        KdTree { nodes, points }
+    }
+
+    /// Builds a tree over the rows of an `n x K` matrix.
+    pub fn from_matrix(matrix: &Matrix<f64>) -> Result<Self, KdTreeError> {
+        if matrix.shape.len() != 2 || matrix.shape[1] != K {
+            return Err(KdTreeError::ShapeMismatch {
+                shape: matrix.shape.clone(),
+                dimensions: K,
+            });
+        }
+        let points = (0..matrix.shape[0])
+            .map(|row| {
+                let mut point = [0.0; K];
+                for (col, coordinate) in point.iter_mut().enumerate() {
+                    *coordinate = matrix[vec![row, col]];
+                }
+                point
+            })
+            .collect();
+        Ok(Self::new(points))
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The point at position `index` of the input.
+    pub fn point(&self, index: usize) -> Option<&[f64; K]> {
+        self.points.get(index)
+    }
+
+    pub fn nearest<M: Metric>(&self, query: &[f64; K], metric: &M) -> Option<Neighbour> {
+        self.k_nearest(query, 1, metric).pop()
+    }
+
+    /// The `k` points closest to `query`, nearest first.
+    pub fn k_nearest<M: Metric>(&self, query: &[f64; K], k: usize, metric: &M) -> Vec<Neighbour> {
+        if k == 0 {
+            return Vec::new();
+        }
+        // Max-heap of the best candidates so far; its top is the one to beat.
+        let mut best: BinaryHeap<Neighbour> = BinaryHeap::with_capacity(k + 1);
+        self.search(&self.nodes, 0, query, metric, &mut |neighbour| {
+            best.push(neighbour);
+            if best.len() > k {
+                best.pop();
+            }
+            if best.len() == k {
+                best.peek().unwrap().distance
+            } else {
+                f64::INFINITY
+            }
+        });
+        best.into_sorted_vec()
+    }
+
+    /// Every point within `radius` of `query` (inclusive), nearest first.
+    pub fn within_radius<M: Metric>(
+        &self,
+        query: &[f64; K],
+        radius: f64,
+        metric: &M,
+    ) -> Vec<Neighbour> {
+        let mut found = Vec::new();
+        self.search(&self.nodes, 0, query, metric, &mut |neighbour| {
+            if neighbour.distance <= radius {
+                found.push(neighbour);
+            }
+            radius
+        });
+        found.sort();
+        found
+    }
+
+    fn build(nodes: &mut [([f64; K], usize)], depth: usize) {
+        if nodes.len() <= 1 {
+            return;
+        }
+        let axis = depth % K;
+        let mid = nodes.len() / 2;
+        nodes.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+        let (left, right) = nodes.split_at_mut(mid);
+        Self::build(left, depth + 1);
+        Self::build(&mut right[1..], depth + 1);
+    }
+
+    /// Offers every point that might qualify to `visit`, which returns the
+    /// distance beyond which no further point is of interest.
+    fn search<M, F>(
+        &self,
+        nodes: &[([f64; K], usize)],
+        depth: usize,
+        query: &[f64; K],
+        metric: &M,
+        visit: &mut F,
+    ) -> f64
+    where
+        M: Metric,
+        F: FnMut(Neighbour) -> f64,
+    {
+        if nodes.is_empty() {
+            return f64::INFINITY;
+        }
+        let mid = nodes.len() / 2;
+        let (point, index) = &nodes[mid];
+        let mut bound = visit(Neighbour {
+            index: *index,
+            distance: metric.distance(query, point),
+        });
+
+        let axis = depth % K;
+        let delta = query[axis] - point[axis];
+        let (near, far) = if delta < 0.0 {
+            (&nodes[..mid], &nodes[mid + 1..])
+        } else {
+            (&nodes[mid + 1..], &nodes[..mid])
+        };
+        if !near.is_empty() {
+            bound = self.search(near, depth + 1, query, metric, visit);
+        }
+        if !far.is_empty() && metric.axis_distance(delta) <= bound {
+            bound = self.search(far, depth + 1, query, metric, visit);
+        }
+        bound
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn brute_force<M: Metric>(points: &[[f64; 3]], query: &[f64; 3], metric: &M) -> Vec<Neighbour> {
+        let mut all: Vec<Neighbour> = points
+            .iter()
+            .enumerate()
+            .map(|(index, point)| Neighbour {
+                index,
+                distance: metric.distance(query, point),
+            })
+            .collect();
+        all.sort();
+        all
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(46);
+        let points: Vec<[f64; 3]> = (0..500)
+            .map(|_| {
+                [
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                ]
+            })
+            .collect();
+        let tree = KdTree::new(points.clone());
+
+        for _ in 0..50 {
+            let query = [
+                rng.gen_range(-12.0..12.0),
+                rng.gen_range(-12.0..12.0),
+                rng.gen_range(-12.0..12.0),
+            ];
+            let expected = brute_force(&points, &query, &Euclidean);
+            assert_eq!(tree.nearest(&query, &Euclidean), Some(expected[0]));
+            assert_eq!(
+                tree.k_nearest(&query, 7, &Euclidean),
+                expected[..7].to_vec()
+            );
+
+            let expected = brute_force(&points, &query, &Manhattan);
+            assert_eq!(
+                tree.k_nearest(&query, 5, &Manhattan),
+                expected[..5].to_vec()
+            );
+
+            let expected: Vec<Neighbour> = brute_force(&points, &query, &Chebyshev)
+                .into_iter()
+                .take_while(|n| n.distance <= 3.0)
+                .collect();
+            assert_eq!(tree.within_radius(&query, 3.0, &Chebyshev), expected);
+        }
+    }
+
+    #[test]
+    fn from_matrix_rows() {
+        let matrix =
+            Matrix::new_with_data(vec![4, 2], vec![0.0, 0.0, 5.0, 5.0, 1.0, 2.0, -3.0, 4.0]);
+        let tree: KdTree<2> = KdTree::from_matrix(&matrix).unwrap();
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree.point(2), Some(&[1.0, 2.0]));
+        let nearest = tree.nearest(&[4.0, 4.5], &Euclidean).unwrap();
+        assert_eq!(nearest.index, 1);
+        assert_eq!(tree.k_nearest(&[0.0, 0.0], 10, &Manhattan).len(), 4);
+
+        assert_eq!(
+            KdTree::<3>::from_matrix(&matrix).err(),
+            Some(KdTreeError::ShapeMismatch {
+                shape: vec![4, 2],
+                dimensions: 3
+            })
+        );
+    }
+}
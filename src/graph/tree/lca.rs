@@ -0,0 +1,176 @@
+//! Lowest common ancestor queries by binary lifting.
+//!
+//! Preprocessing records, for every node, its ancestors `2^j` levels up. Any
+//! ancestor can then be reached in O(log n) jumps, and the lowest common
+//! ancestor of two nodes is found by lifting both to the same depth and then
+//! jumping up together for as long as they stay apart.
+
+use super::Tree;
+use std::collections::HashMap;
+
+/// Snapshot of a `Tree` prepared for ancestor queries. Later changes to the
+/// tree are not reflected.
+pub struct LcaIndex {
+    ids: Vec<usize>,
+    index: HashMap<usize, usize>,
+    depth: Vec<usize>,
+    /// `up[j][i]` is the ancestor `2^j` levels above node `i`, or the root if
+    /// that would overshoot.
+    up: Vec<Vec<usize>>,
+}
+
+impl LcaIndex {
+    /// Preprocesses `tree` in O(n log n).
+    pub fn new(tree: &Tree) -> Self {
+        // Level order visits every parent before its children.
+        let ids: Vec<usize> = tree.level_order().collect();
+        let index: HashMap<usize, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+        let mut parent = vec![0; ids.len()];
+        let mut depth = vec![0; ids.len()];
+        for (i, id) in ids.iter().enumerate().skip(1) {
+            let p = index[&tree.parent(*id).unwrap()];
+            parent[i] = p;
+            depth[i] = depth[p] + 1;
+        }
+
+        let levels = (usize::BITS - ids.len().leading_zeros()).max(1) as usize;
+        let mut up = vec![parent];
+        for j in 1..levels {
+            let previous = &up[j - 1];
+            let next = previous.iter().map(|p| previous[*p]).collect();
+            up.push(next);
+        }
+
+        LcaIndex {
+            ids,
+            index,
+            depth,
+            up,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn depth(&self, id: usize) -> Option<usize> {
+        self.index.get(&id).map(|i| self.depth[*i])
+    }
+
+    /// Deepest node that is an ancestor of (or equal to) both `a` and `b`.
+    pub fn lca(&self, a: usize, b: usize) -> Option<usize> {
+        let (a, b) = (*self.index.get(&a)?, *self.index.get(&b)?);
+        Some(self.ids[self.lca_index(a, b)])
+    }
+
+    /// Number of edges on the path between `a` and `b`.
+    pub fn distance(&self, a: usize, b: usize) -> Option<usize> {
+        let (a, b) = (*self.index.get(&a)?, *self.index.get(&b)?);
+        let lca = self.lca_index(a, b);
+        Some(self.depth[a] + self.depth[b] - 2 * self.depth[lca])
+    }
+
+    /// Ancestor `k` levels above `id`; `k = 0` gives `id` itself. `None` if
+    /// `id` is unknown or less than `k` levels deep.
+    pub fn kth_ancestor(&self, id: usize, k: usize) -> Option<usize> {
+        let i = *self.index.get(&id)?;
+        if k > self.depth[i] {
+            return None;
+        }
+        Some(self.ids[self.lift(i, k)])
+    }
+
+    fn lift(&self, mut i: usize, k: usize) -> usize {
+        for (j, up) in self.up.iter().enumerate() {
+            if k >> j & 1 == 1 {
+                i = up[i];
+            }
+        }
+        i
+    }
+
+    fn lca_index(&self, a: usize, b: usize) -> usize {
+        let (mut a, mut b) = if self.depth[a] >= self.depth[b] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        a = self.lift(a, self.depth[a] - self.depth[b]);
+        if a == b {
+            return a;
+        }
+        for up in self.up.iter().rev() {
+            if up[a] != up[b] {
+                a = up[a];
+                b = up[b];
+            }
+        }
+        self.up[0][a]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //          0
+    //        /   \
+    //      10     20
+    //     /  \      \
+    //   11    12     21
+    //   |             \
+    //   13             22
+    fn setup_tree() -> Tree {
+        let mut tree = Tree::new(0);
+        for (parent, id) in [
+            (0, 10),
+            (0, 20),
+            (10, 11),
+            (10, 12),
+            (20, 21),
+            (11, 13),
+            (21, 22),
+        ]
+        .iter()
+        {
+            tree.add_child(*parent, *id).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn lowest_common_ancestors() {
+        let index = LcaIndex::new(&setup_tree());
+        assert_eq!(index.lca(13, 12), Some(10));
+        assert_eq!(index.lca(13, 22), Some(0));
+        assert_eq!(index.lca(11, 13), Some(11));
+        assert_eq!(index.lca(22, 22), Some(22));
+        assert_eq!(index.lca(13, 99), None);
+
+        assert_eq!(index.distance(13, 22), Some(6));
+        assert_eq!(index.distance(12, 13), Some(3));
+        assert_eq!(index.distance(0, 0), Some(0));
+    }
+
+    #[test]
+    fn kth_ancestor() {
+        let index = LcaIndex::new(&setup_tree());
+        assert_eq!(index.kth_ancestor(22, 0), Some(22));
+        assert_eq!(index.kth_ancestor(22, 2), Some(20));
+        assert_eq!(index.kth_ancestor(22, 3), Some(0));
+        assert_eq!(index.kth_ancestor(22, 4), None);
+    }
+
+    #[test]
+    fn deep_path() {
+        let mut tree = Tree::new(0);
+        for id in 1..1000 {
+            tree.add_child(id - 1, id).unwrap();
+        }
+        let index = LcaIndex::new(&tree);
+        assert_eq!(index.lca(999, 500), Some(500));
+        assert_eq!(index.kth_ancestor(999, 777), Some(222));
+        assert_eq!(index.distance(3, 999), Some(996));
+    }
+}
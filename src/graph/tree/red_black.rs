@@ -0,0 +1,478 @@
+//! Red-black tree ordered map with optional order statistics.
+//!
+//! This is the left-leaning variant of the red-black tree (Sedgewick, 2008),
+//! which corresponds one-to-one with 2-3 trees: red links always lean left and
+//! no node has two red links attached. Every root-to-leaf path crosses the
+//! same number of black links, so the height stays below 2 log n.
+//!
+//! Trees created with `with_order_statistics` additionally keep the size of
+//! every subtree up to date, which lets `rank` and `select` run in O(log n)
+//! (CLRS 14.1).
+
+use super::bst::{self, BinaryNode, InvariantError, Iter};
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::RangeBounds;
+
+type RbLink<K, V> = Option<Box<RbNode<K, V>>>;
+
+pub struct RbNode<K, V> {
+    key: K,
+    value: V,
+    /// Colour of the link from the parent to this node.
+    red: bool,
+    /// Number of nodes in this subtree; only maintained with order statistics.
+    size: usize,
+    left: RbLink<K, V>,
+    right: RbLink<K, V>,
+}
+
+impl<K, V> BinaryNode for RbNode<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+fn is_red<K, V>(link: &RbLink<K, V>) -> bool {
+    link.as_ref().is_some_and(|node| node.red)
+}
+
+fn size<K, V>(link: &RbLink<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+/// Left-leaning red-black tree mapping ordered keys to values.
+pub struct RedBlackTree<K, V> {
+    root: RbLink<K, V>,
+    len: usize,
+    order_statistics: bool,
+}
+
+impl<K: Ord, V> RedBlackTree<K, V> {
+    pub fn new() -> Self {
+        RedBlackTree {
+            root: None,
+            len: 0,
+            order_statistics: false,
+        }
+    }
+
+    /// Creates a tree that maintains subtree sizes for `rank` and `select`.
+    pub fn with_order_statistics() -> Self {
+        RedBlackTree {
+            order_statistics: true,
+            ..Self::new()
+        }
+    }
+
+    pub fn has_order_statistics(&self) -> bool {
+        self.order_statistics
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        bst::search(self.root.as_deref(), key).map(|node| &node.value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut replaced = None;
+        let root = self.root.take();
+        let mut root = self.insert_at(root, key, value, &mut replaced);
+        root.red = false;
+        self.root = Some(root);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        if !self.contains_key(key) {
+            return None;
+        }
+        let mut root = self.root.take().unwrap();
+        if !is_red(&root.left) && !is_red(&root.right) {
+            root.red = true;
+        }
+        let mut removed = None;
+        self.root = self.remove_at(root, key, &mut removed);
+        if let Some(root) = self.root.as_mut() {
+            root.red = false;
+        }
+        self.len -= 1;
+        removed
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        bst::first(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        bst::last(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    /// Entry with the greatest key less than or equal to `key`.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        bst::floor(self.root.as_deref(), key).map(|node| (&node.key, &node.value))
+    }
+
+    /// Entry with the least key greater than or equal to `key`.
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        bst::ceiling(self.root.as_deref(), key).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn iter(&self) -> Iter<'_, RbNode<K, V>> {
+        Iter::new(self.root.as_deref(), &(..))
+    }
+
+    /// Entries whose keys lie within `range`, in ascending key order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, RbNode<K, V>> {
+        Iter::new(self.root.as_deref(), &range)
+    }
+
+    /// Number of keys strictly less than `key`, or `None` if the tree does not
+    /// keep order statistics.
+    pub fn rank(&self, key: &K) -> Option<usize> {
+        if !self.order_statistics {
+            return None;
+        }
+        let mut rank = 0;
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                Ordering::Less => node = n.left.as_deref(),
+                Ordering::Greater => {
+                    rank += size(&n.left) + 1;
+                    node = n.right.as_deref();
+                }
+                Ordering::Equal => return Some(rank + size(&n.left)),
+            }
+        }
+        Some(rank)
+    }
+
+    /// The entry with exactly `i` smaller keys, or `None` if `i` is out of
+    /// bounds or the tree does not keep order statistics.
+    pub fn select(&self, mut i: usize) -> Option<(&K, &V)> {
+        if !self.order_statistics {
+            return None;
+        }
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            let left = size(&n.left);
+            match i.cmp(&left) {
+                Ordering::Less => node = n.left.as_deref(),
+                Ordering::Greater => {
+                    i -= left + 1;
+                    node = n.right.as_deref();
+                }
+                Ordering::Equal => return Some((&n.key, &n.value)),
+            }
+        }
+        None
+    }
+
+    /// Checks key order, that the root is black, that red links lean left and
+    /// never follow each other, that every path has the same black height and,
+    /// with order statistics, that every subtree size is correct.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        bst::validate_order(self.root.as_deref())?;
+        if is_red(&self.root) {
+            return Err(InvariantError::RedRoot);
+        }
+        self.validate_node(&self.root).map(|_| ())
+    }
+
+    /// Returns the black height of the subtree.
+    fn validate_node(&self, link: &RbLink<K, V>) -> Result<usize, InvariantError> {
+        let node = match link {
+            Some(node) => node,
+            None => return Ok(0),
+        };
+        if is_red(&node.right) {
+            return Err(InvariantError::RightLeaningRed);
+        }
+        if node.red && is_red(&node.left) {
+            return Err(InvariantError::DoubleRed);
+        }
+        let left = self.validate_node(&node.left)?;
+        let right = self.validate_node(&node.right)?;
+        if left != right {
+            return Err(InvariantError::BlackHeight { left, right });
+        }
+        if self.order_statistics {
+            let expected = size(&node.left) + size(&node.right) + 1;
+            if node.size != expected {
+                return Err(InvariantError::Size {
+                    expected,
+                    found: node.size,
+                });
+            }
+        }
+        Ok(left + if node.red { 0 } else { 1 })
+    }
+
+    fn update_size(&self, node: &mut RbNode<K, V>) {
+        if self.order_statistics {
+            node.size = size(&node.left) + size(&node.right) + 1;
+        }
+    }
+
+    fn rotate_left(&self, mut h: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+        let mut x = h.right.take().expect("rotation needs a right child");
+        h.right = x.left.take();
+        x.red = h.red;
+        h.red = true;
+        self.update_size(&mut h);
+        x.left = Some(h);
+        self.update_size(&mut x);
+        x
+    }
+
+    fn rotate_right(&self, mut h: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+        let mut x = h.left.take().expect("rotation needs a left child");
+        h.left = x.right.take();
+        x.red = h.red;
+        h.red = true;
+        self.update_size(&mut h);
+        x.right = Some(h);
+        self.update_size(&mut x);
+        x
+    }
+
+    fn flip_colours(h: &mut RbNode<K, V>) {
+        h.red = !h.red;
+        for child in [h.left.as_mut(), h.right.as_mut()].iter_mut().flatten() {
+            child.red = !child.red;
+        }
+    }
+
+    /// Restores the left-leaning invariants on the way back up.
+    fn fix_up(&self, mut h: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+        if is_red(&h.right) && !is_red(&h.left) {
+            h = self.rotate_left(h);
+        }
+        if is_red(&h.left) && is_red(&h.left.as_ref().unwrap().left) {
+            h = self.rotate_right(h);
+        }
+        if is_red(&h.left) && is_red(&h.right) {
+            Self::flip_colours(&mut h);
+        }
+        self.update_size(&mut h);
+        h
+    }
+
+    /// Makes `h.left` or one of its children red, assuming `h` is red and both
+    /// `h.left` and `h.left.left` are black.
+    fn move_red_left(&self, mut h: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+        Self::flip_colours(&mut h);
+        if is_red(&h.right.as_ref().unwrap().left) {
+            h.right = h.right.take().map(|right| self.rotate_right(right));
+            h = self.rotate_left(h);
+            Self::flip_colours(&mut h);
+        }
+        h
+    }
+
+    /// Makes `h.right` or one of its children red, assuming `h` is red and
+    /// both `h.right` and `h.right.left` are black.
+    fn move_red_right(&self, mut h: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+        Self::flip_colours(&mut h);
+        if is_red(&h.left.as_ref().unwrap().left) {
+            h = self.rotate_right(h);
+            Self::flip_colours(&mut h);
+        }
+        h
+    }
+
+    fn insert_at(
+        &self,
+        link: RbLink<K, V>,
+        key: K,
+        value: V,
+        replaced: &mut Option<V>,
+    ) -> Box<RbNode<K, V>> {
+        let mut h = match link {
+            Some(node) => node,
+            None => {
+                return Box::new(RbNode {
+                    key,
+                    value,
+                    red: true,
+                    size: 1,
+                    left: None,
+                    right: None,
+                })
+            }
+        };
+        match key.cmp(&h.key) {
+            Ordering::Less => h.left = Some(self.insert_at(h.left.take(), key, value, replaced)),
+            Ordering::Greater => {
+                h.right = Some(self.insert_at(h.right.take(), key, value, replaced))
+            }
+            Ordering::Equal => *replaced = Some(mem::replace(&mut h.value, value)),
+        }
+        self.fix_up(h)
+    }
+
+    /// Removes `key`, which must be present in the subtree below `h`.
+    fn remove_at(
+        &self,
+        mut h: Box<RbNode<K, V>>,
+        key: &K,
+        removed: &mut Option<V>,
+    ) -> RbLink<K, V> {
+        if *key < h.key {
+            if !is_red(&h.left) && !is_red(&h.left.as_ref().unwrap().left) {
+                h = self.move_red_left(h);
+            }
+            h.left = self.remove_at(h.left.take().unwrap(), key, removed);
+        } else {
+            if is_red(&h.left) {
+                h = self.rotate_right(h);
+            }
+            if *key == h.key && h.right.is_none() {
+                *removed = Some(h.value);
+                return None;
+            }
+            if !is_red(&h.right) && !is_red(&h.right.as_ref().unwrap().left) {
+                h = self.move_red_right(h);
+            }
+            if *key == h.key {
+                let (rest, min) = self.remove_min(h.right.take().unwrap());
+                h.right = rest;
+                h.key = min.key;
+                *removed = Some(mem::replace(&mut h.value, min.value));
+            } else {
+                h.right = self.remove_at(h.right.take().unwrap(), key, removed);
+            }
+        }
+        Some(self.fix_up(h))
+    }
+
+    /// Splits off the leftmost node, returning the rebalanced remainder and that node.
+    fn remove_min(&self, mut h: Box<RbNode<K, V>>) -> (RbLink<K, V>, Box<RbNode<K, V>>) {
+        if h.left.is_none() {
+            return (h.right.take(), h);
+        }
+        if !is_red(&h.left) && !is_red(&h.left.as_ref().unwrap().left) {
+            h = self.move_red_left(h);
+        }
+        let (rest, min) = self.remove_min(h.left.take().unwrap());
+        h.left = rest;
+        (Some(self.fix_up(h)), min)
+    }
+}
+
+impl<K: Ord, V> Default for RedBlackTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for RedBlackTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = RedBlackTree::new();
+        for (key, value) in iter {
+            tree.insert(key, value);
+        }
+        tree
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a RedBlackTree<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, RbNode<K, V>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::bst::BstMap;
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn matches_reference_bst() {
+        let mut rng = StdRng::seed_from_u64(35);
+        let mut tree: RedBlackTree<i32, i32> = RedBlackTree::with_order_statistics();
+        let mut reference: BstMap<i32, i32> = BstMap::new();
+
+        for step in 0..3000 {
+            let key = rng.gen_range(0..400);
+            if rng.gen_bool(0.55) {
+                assert_eq!(tree.insert(key, step), reference.insert(key, step));
+            } else {
+                assert_eq!(tree.remove(&key), reference.remove(&key));
+            }
+            if step % 100 == 0 {
+                assert_eq!(tree.validate(), Ok(()));
+            }
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.len(), reference.len());
+        assert!(tree.iter().eq(reference.iter()));
+        assert!(tree.range(100..=200).eq(reference.range(100..=200)));
+    }
+
+    #[test]
+    fn rank_and_select() {
+        let mut scores: RedBlackTree<u32, &str> = RedBlackTree::with_order_statistics();
+        for (score, name) in [(70, "b"), (95, "a"), (40, "d"), (88, "c"), (12, "e")].iter() {
+            scores.insert(*score, *name);
+        }
+        assert_eq!(scores.rank(&12), Some(0));
+        assert_eq!(scores.rank(&88), Some(3));
+        assert_eq!(scores.rank(&90), Some(4));
+        assert_eq!(scores.select(1), Some((&40, &"d")));
+        assert_eq!(scores.select(4), Some((&95, &"a")));
+        assert_eq!(scores.select(5), None);
+
+        scores.remove(&40);
+        assert_eq!(scores.rank(&88), Some(2));
+        assert_eq!(scores.select(1), Some((&70, &"b")));
+        assert_eq!(scores.validate(), Ok(()));
+    }
+
+    #[test]
+    fn without_order_statistics() {
+        let tree: RedBlackTree<u32, ()> = (0..1000).map(|x| (x, ())).collect();
+        assert!(!tree.has_order_statistics());
+        assert_eq!(tree.rank(&10), None);
+        assert_eq!(tree.select(10), None);
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.floor(&2000), Some((&999, &())));
+    }
+}
@@ -0,0 +1,354 @@
+//! Segment trees over user-defined monoids.
+//!
+//! [`SegmentTree`] answers range aggregates under point updates in O(log n).
+//! [`LazySegmentTree`] additionally applies an [`Action`] to whole ranges,
+//! deferring the work on a node's children until a later operation needs them.
+//! Neither requires the monoid to be commutative: aggregates are always
+//! combined in index order.
+
+use super::bounds;
+use crate::math::algebra::{Action, Monoid};
+use std::ops::RangeBounds;
+
+/// Bottom-up segment tree with point updates and range queries.
+pub struct SegmentTree<M: Monoid> {
+    monoid: M,
+    len: usize,
+    /// Leaves live at `len..2 * len`; node `i` aggregates nodes `2i` and `2i + 1`.
+    nodes: Vec<M::Item>,
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    /// A tree of `len` identity elements.
+    pub fn new(monoid: M, len: usize) -> Self {
+        let values = vec![monoid.identity(); len];
+        Self::from_vec(monoid, values)
+    }
+
+    pub fn from_vec(monoid: M, values: Vec<M::Item>) -> Self {
+        let len = values.len();
+        let mut nodes = vec![monoid.identity(); len];
+        nodes.extend(values);
+        for i in (1..len).rev() {
+            nodes[i] = monoid.combine(&nodes[2 * i], &nodes[2 * i + 1]);
+        }
+        SegmentTree { monoid, len, nodes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&M::Item> {
+        if index < self.len {
+            Some(&self.nodes[self.len + index])
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn set(&mut self, index: usize, value: M::Item) {
+        assert!(
+            index < self.len,
+            "index {} is out of bounds for length {}",
+            index,
+            self.len
+        );
+        let mut i = index + self.len;
+        self.nodes[i] = value;
+        while i > 1 {
+            i /= 2;
+            self.nodes[i] = self
+                .monoid
+                .combine(&self.nodes[2 * i], &self.nodes[2 * i + 1]);
+        }
+    }
+
+    /// Combines the elements within `range` in index order.
+    pub fn query<R: RangeBounds<usize>>(&self, range: R) -> M::Item {
+        let (start, end) = bounds(&range, self.len);
+        let (mut left, mut right) = (start + self.len, end + self.len);
+        let mut prefix = self.monoid.identity();
+        let mut suffix = self.monoid.identity();
+        while left < right {
+            if left % 2 == 1 {
+                prefix = self.monoid.combine(&prefix, &self.nodes[left]);
+                left += 1;
+            }
+            if right % 2 == 1 {
+                right -= 1;
+                suffix = self.monoid.combine(&self.nodes[right], &suffix);
+            }
+            left /= 2;
+            right /= 2;
+        }
+        self.monoid.combine(&prefix, &suffix)
+    }
+}
+
+/// Segment tree supporting range updates through the action `A` alongside
+/// range queries.
+pub struct LazySegmentTree<M: Monoid, A: Action<M>> {
+    monoid: M,
+    action: A,
+    len: usize,
+    /// Number of leaves, rounded up to a power of two.
+    width: usize,
+    nodes: Vec<M::Item>,
+    /// Updates already reflected in `nodes[i]` but not yet in its children.
+    pending: Vec<A::Update>,
+}
+
+impl<M: Monoid, A: Action<M>> LazySegmentTree<M, A> {
+    /// A tree of `len` identity elements.
+    pub fn new(monoid: M, action: A, len: usize) -> Self {
+        let values = vec![monoid.identity(); len];
+        Self::from_vec(monoid, action, values)
+    }
+
+    pub fn from_vec(monoid: M, action: A, values: Vec<M::Item>) -> Self {
+        let len = values.len();
+        let width = len.next_power_of_two();
+        let mut nodes = vec![monoid.identity(); width];
+        nodes.extend(values);
+        nodes.resize(2 * width, monoid.identity());
+        for i in (1..width).rev() {
+            nodes[i] = monoid.combine(&nodes[2 * i], &nodes[2 * i + 1]);
+        }
+        let pending = vec![action.identity(); width];
+        LazySegmentTree {
+            monoid,
+            action,
+            len,
+            width,
+            nodes,
+            pending,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn monoid(&self) -> &M {
+        &self.monoid
+    }
+
+    pub fn get(&mut self, index: usize) -> Option<M::Item> {
+        if index < self.len {
+            Some(self.query(index..=index))
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn set(&mut self, index: usize, value: M::Item) {
+        assert!(
+            index < self.len,
+            "index {} is out of bounds for length {}",
+            index,
+            self.len
+        );
+        self.set_at(1, 0, self.width, index, value);
+    }
+
+    /// Combines the elements within `range` in index order.
+    pub fn query<R: RangeBounds<usize>>(&mut self, range: R) -> M::Item {
+        let (start, end) = bounds(&range, self.len);
+        self.query_at(1, 0, self.width, start, end)
+    }
+
+    /// Applies `update` to every element within `range`.
+    pub fn update<R: RangeBounds<usize>>(&mut self, range: R, update: A::Update) {
+        let (start, end) = bounds(&range, self.len);
+        self.update_at(1, 0, self.width, start, end, &update);
+    }
+
+    /// Applies `update` to node `i`, which covers `len` leaves.
+    fn apply(&mut self, i: usize, update: &A::Update, len: usize) {
+        self.nodes[i] = self.action.apply(update, &self.nodes[i], len);
+        if i < self.width {
+            self.pending[i] = self.action.compose(&self.pending[i], update);
+        }
+    }
+
+    fn push_down(&mut self, i: usize, len: usize) {
+        let update = std::mem::replace(&mut self.pending[i], self.action.identity());
+        self.apply(2 * i, &update, len / 2);
+        self.apply(2 * i + 1, &update, len / 2);
+    }
+
+    fn pull_up(&mut self, i: usize) {
+        self.nodes[i] = self
+            .monoid
+            .combine(&self.nodes[2 * i], &self.nodes[2 * i + 1]);
+    }
+
+    fn query_at(&mut self, i: usize, lo: usize, hi: usize, start: usize, end: usize) -> M::Item {
+        if end <= lo || hi <= start {
+            return self.monoid.identity();
+        }
+        if start <= lo && hi <= end {
+            return self.nodes[i].clone();
+        }
+        self.push_down(i, hi - lo);
+        let mid = (lo + hi) / 2;
+        let left = self.query_at(2 * i, lo, mid, start, end);
+        let right = self.query_at(2 * i + 1, mid, hi, start, end);
+        self.monoid.combine(&left, &right)
+    }
+
+    fn update_at(
+        &mut self,
+        i: usize,
+        lo: usize,
+        hi: usize,
+        start: usize,
+        end: usize,
+        update: &A::Update,
+    ) {
+        if end <= lo || hi <= start {
+            return;
+        }
+        if start <= lo && hi <= end {
+            self.apply(i, update, hi - lo);
+            return;
+        }
+        self.push_down(i, hi - lo);
+        let mid = (lo + hi) / 2;
+        self.update_at(2 * i, lo, mid, start, end, update);
+        self.update_at(2 * i + 1, mid, hi, start, end, update);
+        self.pull_up(i);
+    }
+
+    fn set_at(&mut self, i: usize, lo: usize, hi: usize, index: usize, value: M::Item) {
+        if hi - lo == 1 {
+            self.nodes[i] = value;
+            return;
+        }
+        self.push_down(i, hi - lo);
+        let mid = (lo + hi) / 2;
+        if index < mid {
+            self.set_at(2 * i, lo, mid, index, value);
+        } else {
+            self.set_at(2 * i + 1, mid, hi, index, value);
+        }
+        self.pull_up(i);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::algebra::{Add, Assign, FnMonoid, Gcd, Max, Min, Sum};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn point_updates_and_queries() {
+        let mut tree = SegmentTree::from_vec(Sum::new(), vec![5i64, 3, 7, 9, 6, 4, 1, 2]);
+        assert_eq!(tree.query(..), 37);
+        assert_eq!(tree.query(2..5), 22);
+        assert_eq!(tree.query(3..3), 0);
+        tree.set(3, -1);
+        assert_eq!(tree.query(2..=4), 12);
+        assert_eq!(tree.get(3), Some(&-1));
+
+        let mut tree = SegmentTree::from_vec(Gcd::new(), vec![12i32, 18, 27, 36, 8]);
+        assert_eq!(tree.query(0..2), 6);
+        assert_eq!(tree.query(1..4), 9);
+        tree.set(2, 24);
+        assert_eq!(tree.query(0..4), 6);
+
+        let maxes = SegmentTree::from_vec(Max::new(), vec![3u8, 1, 4, 1, 5, 9, 2, 6, 5]);
+        assert_eq!(maxes.query(..5), 5);
+        assert_eq!(maxes.query(6..), 6);
+    }
+
+    #[test]
+    fn combines_in_index_order() {
+        let concat = FnMonoid::new(String::new(), |a: &String, b: &String| {
+            format!("{}{}", a, b)
+        });
+        let letters = "segment".chars().map(|c| c.to_string()).collect();
+        let mut tree = SegmentTree::from_vec(concat, letters);
+        assert_eq!(tree.query(1..6), "egmen");
+        tree.set(0, "S".to_string());
+        assert_eq!(tree.query(..), "Segment");
+    }
+
+    #[test]
+    fn lazy_add_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(39);
+        let mut values: Vec<i64> = (0..37).map(|_| rng.gen_range(-50..50)).collect();
+        let mut sums = LazySegmentTree::from_vec(Sum::new(), Add::new(), values.clone());
+        let mut mins = LazySegmentTree::from_vec(Min::new(), Add::new(), values.clone());
+
+        for _ in 0..500 {
+            let start = rng.gen_range(0..values.len());
+            let end = rng.gen_range(start..=values.len());
+            if rng.gen_bool(0.5) {
+                let delta = rng.gen_range(-10..10);
+                sums.update(start..end, delta);
+                mins.update(start..end, delta);
+                values[start..end].iter_mut().for_each(|v| *v += delta);
+            } else {
+                assert_eq!(
+                    sums.query(start..end),
+                    values[start..end].iter().sum::<i64>()
+                );
+                let min = values[start..end].iter().cloned().min().unwrap_or(i64::MAX);
+                assert_eq!(mins.query(start..end), min);
+            }
+        }
+    }
+
+    #[test]
+    fn lazy_add_leaves_identities_alone() {
+        let mut mins = LazySegmentTree::new(Min::new(), Add::new(), 6);
+        mins.update(.., 1i32);
+        assert_eq!(mins.query(..), i32::MAX);
+        mins.set(2, 4);
+        mins.update(1..4, 1);
+        assert_eq!(mins.query(..), 5);
+
+        let mut maxes = LazySegmentTree::new(Max::new(), Add::new(), 6);
+        maxes.update(.., -1i32);
+        assert_eq!(maxes.query(..), i32::MIN);
+    }
+
+    #[test]
+    fn lazy_assign() {
+        let mut sums = LazySegmentTree::new(Sum::new(), Assign::new(), 10);
+        sums.update(2..8, Some(3u64));
+        sums.update(4..6, Some(10));
+        assert_eq!(sums.query(..), 4 * 3 + 2 * 10);
+        sums.set(2, 1);
+        assert_eq!(sums.query(0..4), 4);
+        assert_eq!(sums.get(5), Some(10));
+
+        let mut mins = LazySegmentTree::from_vec(Min::new(), Assign::new(), vec![5i32, 8, 2, 9, 4]);
+        mins.update(1..3, Some(6));
+        assert_eq!(mins.query(..), 4);
+        mins.update(4.., Some(7));
+        assert_eq!(mins.query(..), 5);
+    }
+}
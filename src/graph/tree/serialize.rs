@@ -0,0 +1,475 @@
+//! Newick and Graphviz DOT serialization for trees.
+//!
+//! Newick writes a tree as nested parentheses, e.g. `((A:0.1,B:0.2)C,D);`,
+//! where every node may carry a label and a branch length to its parent.
+//! Parsing numbers nodes in pre-order starting from 0 at the root. Quoted
+//! labels (`'like this'`), underscores standing in for spaces in unquoted
+//! labels, and `[comments]` are all understood.
+
+use super::Tree;
+use snafu::Snafu;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum NewickError {
+    #[snafu(display(
+        "Expected {} but found '{}' at line {}, column {}",
+        expected,
+        found,
+        line,
+        column
+    ))]
+    UnexpectedChar {
+        expected: &'static str,
+        found: char,
+        line: usize,
+        column: usize,
+    },
+    #[snafu(display(
+        "Expected {} but the input ended at line {}, column {}",
+        expected,
+        line,
+        column
+    ))]
+    UnexpectedEnd {
+        expected: &'static str,
+        line: usize,
+        column: usize,
+    },
+    #[snafu(display("Invalid branch length '{}' at line {}, column {}", text, line, column))]
+    InvalidLength {
+        text: String,
+        line: usize,
+        column: usize,
+    },
+}
+
+/// A tree along with the node labels and branch lengths Newick can carry.
+///
+/// Both maps are keyed by node id; `lengths[id]` is the length of the branch
+/// from `id` up to its parent (or, for the root, the length given after it).
+pub struct AnnotatedTree {
+    pub tree: Tree,
+    pub labels: HashMap<usize, String>,
+    pub lengths: HashMap<usize, f64>,
+}
+
+impl AnnotatedTree {
+    pub fn from_newick(input: &str) -> Result<Self, NewickError> {
+        let mut parser = Parser {
+            chars: input.chars().peekable(),
+            line: 1,
+            column: 1,
+            tree: None,
+            labels: HashMap::new(),
+            lengths: HashMap::new(),
+            next_id: 0,
+        };
+        parser.parse_tree()?;
+        parser.expect(';', "';'")?;
+        parser.skip_insignificant()?;
+        if let Some(found) = parser.chars.peek().copied() {
+            return Err(parser.unexpected("the end of the input", found));
+        }
+        Ok(AnnotatedTree {
+            tree: parser.tree.unwrap(),
+            labels: parser.labels,
+            lengths: parser.lengths,
+        })
+    }
+
+    pub fn to_newick(&self) -> String {
+        let mut out = String::new();
+        self.write_newick(self.tree.root(), &mut out);
+        out.push(';');
+        out
+    }
+
+    /// Graphviz description of the tree, with edges pointing from parent to
+    /// child and branch lengths as edge labels.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph tree {\n");
+        for id in self.tree.pre_order() {
+            match self.labels.get(&id) {
+                Some(label) => {
+                    writeln!(out, "    {} [label=\"{}\"];", id, escape_dot(label)).unwrap()
+                }
+                None => writeln!(out, "    {};", id).unwrap(),
+            }
+        }
+        for id in self.tree.pre_order() {
+            for child in self.tree.children(id) {
+                match self.lengths.get(&child) {
+                    Some(length) => {
+                        writeln!(out, "    {} -> {} [label=\"{}\"];", id, child, length).unwrap()
+                    }
+                    None => writeln!(out, "    {} -> {};", id, child).unwrap(),
+                }
+            }
+        }
+        out.push('}');
+        out.push('\n');
+        out
+    }
+
+    fn write_newick(&self, root: usize, out: &mut String) {
+        let mut stack = vec![Step::Open(root)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Open(id) => {
+                    let children = self.tree.children(id);
+                    if children.is_empty() {
+                        self.write_annotation(id, out);
+                        continue;
+                    }
+                    out.push('(');
+                    stack.push(Step::Close(id));
+                    for (i, child) in children.into_iter().enumerate().rev() {
+                        stack.push(Step::Open(child));
+                        if i > 0 {
+                            stack.push(Step::Separator);
+                        }
+                    }
+                }
+                Step::Separator => out.push(','),
+                Step::Close(id) => {
+                    out.push(')');
+                    self.write_annotation(id, out);
+                }
+            }
+        }
+    }
+
+    /// Writes the label and branch length that follow a node.
+    fn write_annotation(&self, id: usize, out: &mut String) {
+        if let Some(label) = self.labels.get(&id) {
+            write_label(label, out);
+        }
+        if let Some(length) = self.lengths.get(&id) {
+            write!(out, ":{}", length).unwrap();
+        }
+    }
+}
+
+impl From<Tree> for AnnotatedTree {
+    fn from(tree: Tree) -> Self {
+        AnnotatedTree {
+            tree,
+            labels: HashMap::new(),
+            lengths: HashMap::new(),
+        }
+    }
+}
+
+/// Pending output while writing Newick without recursion.
+enum Step {
+    /// A node still to be written, children first.
+    Open(usize),
+    Separator,
+    /// A node whose children have all been written.
+    Close(usize),
+}
+
+/// Characters that end an unquoted Newick label.
+fn is_delimiter(c: char) -> bool {
+    "()[]':;,".contains(c) || c.is_whitespace()
+}
+
+fn write_label(label: &str, out: &mut String) {
+    if label.is_empty() || label.contains(|c: char| is_delimiter(c) || c == '_') {
+        out.push('\'');
+        out.push_str(&label.replace('\'', "''"));
+        out.push('\'');
+    } else {
+        out.push_str(label);
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    tree: Option<Tree>,
+    labels: HashMap<usize, String>,
+    lengths: HashMap<usize, f64>,
+    next_id: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn unexpected(&self, expected: &'static str, found: char) -> NewickError {
+        NewickError::UnexpectedChar {
+            expected,
+            found,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn end(&self, expected: &'static str) -> NewickError {
+        NewickError::UnexpectedEnd {
+            expected,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// Skips whitespace and bracketed comments.
+    fn skip_insignificant(&mut self) -> Result<(), NewickError> {
+        while let Some(c) = self.chars.peek().copied() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '[' {
+                while self.bump() != Some(']') {
+                    if self.chars.peek().is_none() {
+                        return Err(self.end("']' to close the comment"));
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<char>, NewickError> {
+        self.skip_insignificant()?;
+        Ok(self.chars.peek().copied())
+    }
+
+    fn expect(&mut self, wanted: char, expected: &'static str) -> Result<(), NewickError> {
+        match self.peek()? {
+            Some(c) if c == wanted => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.unexpected(expected, c)),
+            None => Err(self.end(expected)),
+        }
+    }
+
+    fn add_node(&mut self, parent: Option<usize>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        match (parent, self.tree.as_mut()) {
+            (Some(parent), Some(tree)) => tree.add_child(parent, id).unwrap(),
+            _ => self.tree = Some(Tree::new(id)),
+        }
+        id
+    }
+
+    /// Parses the whole tree, keeping the nodes whose child lists are still
+    /// open on an explicit stack so that deep nesting cannot overflow.
+    fn parse_tree(&mut self) -> Result<(), NewickError> {
+        let mut open: Vec<usize> = Vec::new();
+        let mut id = self.add_node(None);
+        loop {
+            if self.peek()? == Some('(') {
+                self.bump();
+                open.push(id);
+                id = self.add_node(Some(id));
+                continue;
+            }
+            self.parse_annotation(id)?;
+            // Close every list this node ends, then start its next sibling.
+            loop {
+                let parent = match open.last() {
+                    Some(parent) => *parent,
+                    None => return Ok(()),
+                };
+                match self.peek()? {
+                    Some(',') => {
+                        self.bump();
+                        id = self.add_node(Some(parent));
+                        break;
+                    }
+                    Some(')') => {
+                        self.bump();
+                        open.pop();
+                        self.parse_annotation(parent)?;
+                    }
+                    Some(c) => return Err(self.unexpected("',' or ')'", c)),
+                    None => return Err(self.end("',' or ')'")),
+                }
+            }
+        }
+    }
+
+    /// Parses the label and branch length that follow a node.
+    fn parse_annotation(&mut self, id: usize) -> Result<(), NewickError> {
+        if let Some(label) = self.parse_label()? {
+            self.labels.insert(id, label);
+        }
+        if self.peek()? == Some(':') {
+            self.bump();
+            let length = self.parse_length()?;
+            self.lengths.insert(id, length);
+        }
+        Ok(())
+    }
+
+    fn parse_label(&mut self) -> Result<Option<String>, NewickError> {
+        let mut label = String::new();
+        match self.peek()? {
+            Some('\'') => {
+                self.bump();
+                loop {
+                    match self.bump() {
+                        Some('\'') if self.chars.peek() == Some(&'\'') => {
+                            self.bump();
+                            label.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => label.push(c),
+                        None => return Err(self.end("a closing quote")),
+                    }
+                }
+            }
+            Some(c) if !is_delimiter(c) => {
+                while let Some(c) = self.chars.peek().copied() {
+                    if is_delimiter(c) {
+                        break;
+                    }
+                    self.bump();
+                    label.push(if c == '_' { ' ' } else { c });
+                }
+            }
+            _ => return Ok(None),
+        }
+        Ok(Some(label))
+    }
+
+    fn parse_length(&mut self) -> Result<f64, NewickError> {
+        self.skip_insignificant()?;
+        let (line, column) = (self.line, self.column);
+        let mut text = String::new();
+        while let Some(c) = self.chars.peek().copied() {
+            if is_delimiter(c) {
+                break;
+            }
+            self.bump();
+            text.push(c);
+        }
+        if text.is_empty() {
+            return match self.chars.peek().copied() {
+                Some(c) => Err(self.unexpected("a branch length", c)),
+                None => Err(self.end("a branch length")),
+            };
+        }
+        text.parse()
+            .map_err(|_| NewickError::InvalidLength { text, line, column })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_labels_and_lengths() {
+        let parsed =
+            AnnotatedTree::from_newick("((A:0.1,B:0.2)C:0.5,'D e''s':1.5,F_G)root;").unwrap();
+        let tree = &parsed.tree;
+        assert_eq!(tree.size(), 6);
+        assert_eq!(tree.children(0), vec![1, 4, 5]);
+        assert_eq!(tree.children(1), vec![2, 3]);
+        assert_eq!(parsed.labels[&0], "root");
+        assert_eq!(parsed.labels[&1], "C");
+        assert_eq!(parsed.labels[&4], "D e's");
+        assert_eq!(parsed.labels[&5], "F G");
+        assert_eq!(parsed.lengths[&3], 0.2);
+        assert_eq!(parsed.lengths.get(&5), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let input = "(,(A,B)[inner comment]:2,'it''s':0.25)'x y';";
+        let parsed = AnnotatedTree::from_newick(input).unwrap();
+        let written = parsed.to_newick();
+        assert_eq!(written, "(,(A,B):2,'it''s':0.25)'x y';");
+        let reparsed = AnnotatedTree::from_newick(&written).unwrap();
+        assert_eq!(
+            reparsed.tree.pre_order().collect::<Vec<_>>(),
+            parsed.tree.pre_order().collect::<Vec<_>>()
+        );
+        assert_eq!(reparsed.labels, parsed.labels);
+        assert_eq!(reparsed.lengths, parsed.lengths);
+    }
+
+    #[test]
+    fn errors_report_position() {
+        assert_eq!(
+            AnnotatedTree::from_newick("(A,B;").err(),
+            Some(NewickError::UnexpectedChar {
+                expected: "',' or ')'",
+                found: ';',
+                line: 1,
+                column: 5
+            })
+        );
+        assert_eq!(
+            AnnotatedTree::from_newick("(A:0.1,\n B:x1)C;").err(),
+            Some(NewickError::InvalidLength {
+                text: "x1".to_string(),
+                line: 2,
+                column: 4
+            })
+        );
+        assert_eq!(
+            AnnotatedTree::from_newick("(A,B)").err(),
+            Some(NewickError::UnexpectedEnd {
+                expected: "';'",
+                line: 1,
+                column: 6
+            })
+        );
+        assert!(AnnotatedTree::from_newick("A;B").is_err());
+    }
+
+    #[test]
+    fn deep_nesting_does_not_recurse() {
+        let depth = 100_000;
+        let input = format!("{}A{};", "(".repeat(depth), ")".repeat(depth));
+        let parsed = AnnotatedTree::from_newick(&input).unwrap();
+        assert_eq!(parsed.tree.size(), depth + 1);
+        assert_eq!(parsed.labels[&depth], "A");
+        assert_eq!(parsed.to_newick(), input);
+
+        let unclosed = format!("{}A;", "(".repeat(depth));
+        assert!(AnnotatedTree::from_newick(&unclosed).is_err());
+    }
+
+    #[test]
+    fn dot_export() {
+        let parsed = AnnotatedTree::from_newick("(\"quoted\":1,B)R;").unwrap();
+        assert_eq!(
+            parsed.to_dot(),
+            "digraph tree {\n    0 [label=\"R\"];\n    1 [label=\"\\\"quoted\\\"\"];\n    2 [label=\"B\"];\n    \
+             0 -> 1 [label=\"1\"];\n    0 -> 2;\n}\n"
+        );
+
+        let mut plain = Tree::new(7);
+        plain.add_child(7, 8).unwrap();
+        assert_eq!(
+            AnnotatedTree::from(plain).to_dot(),
+            "digraph tree {\n    7;\n    8;\n    7 -> 8;\n}\n"
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! Splay tree ordered map.
+//!
+//! A self-adjusting binary search tree (Sleator and Tarjan, 1985): every access
+//! rotates the node it reaches to the root, so recently and frequently used keys
+//! stay near the top. There is no balance information, yet any sequence of m
+//! operations costs O(m log n) in total.
+
+use super::bst::{self, BinaryNode, Iter};
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::RangeBounds;
+
+type SplayLink<K, V> = Option<Box<SplayNode<K, V>>>;
+
+pub struct SplayNode<K, V> {
+    key: K,
+    value: V,
+    /// Number of nodes in the subtree rooted here.
+    size: usize,
+    left: SplayLink<K, V>,
+    right: SplayLink<K, V>,
+}
+
+impl<K, V> BinaryNode for SplayNode<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+impl<K, V> SplayNode<K, V> {
+    fn update_size(&mut self) {
+        self.size = 1 + link_size(&self.left) + link_size(&self.right);
+    }
+}
+
+fn link_size<K, V>(link: &SplayLink<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+/// Top-down splay: brings the node `target` leads to (by comparing against each
+/// key on the way down) to the root. If the target is absent, the last node
+/// on the search path becomes the root instead.
+///
+/// Nodes passed on the way down collect in a left tree (smaller keys) and a
+/// right tree (larger keys) that are reassembled below the new root, so no
+/// recursion is needed even on degenerate trees.
+fn splay<K, V, F>(mut root: Box<SplayNode<K, V>>, mut target: F) -> Box<SplayNode<K, V>>
+where
+    F: FnMut(&K) -> Ordering,
+{
+    // Both trees are kept as their spines: each node still has to have its
+    // right (respectively left) child filled in by the next one.
+    let mut smaller: Vec<Box<SplayNode<K, V>>> = Vec::new();
+    let mut larger: Vec<Box<SplayNode<K, V>>> = Vec::new();
+
+    loop {
+        match target(&root.key) {
+            Ordering::Equal => break,
+            Ordering::Less => {
+                let mut left = match root.left.take() {
+                    Some(left) => left,
+                    None => break,
+                };
+                if target(&left.key) == Ordering::Less {
+                    // Zig-zig: rotate right before linking.
+                    root.left = left.right.take();
+                    root.update_size();
+                    left.right = Some(root);
+                    root = left;
+                    left = match root.left.take() {
+                        Some(left) => left,
+                        None => break,
+                    };
+                }
+                larger.push(mem::replace(&mut root, left));
+            }
+            Ordering::Greater => {
+                let mut right = match root.right.take() {
+                    Some(right) => right,
+                    None => break,
+                };
+                if target(&right.key) == Ordering::Greater {
+                    // Zag-zag: rotate left before linking.
+                    root.right = right.left.take();
+                    root.update_size();
+                    right.left = Some(root);
+                    root = right;
+                    right = match root.right.take() {
+                        Some(right) => right,
+                        None => break,
+                    };
+                }
+                smaller.push(mem::replace(&mut root, right));
+            }
+        }
+    }
+
+    let mut left = root.left.take();
+    while let Some(mut node) = smaller.pop() {
+        node.right = left;
+        node.update_size();
+        left = Some(node);
+    }
+    let mut right = root.right.take();
+    while let Some(mut node) = larger.pop() {
+        node.left = right;
+        node.update_size();
+        right = Some(node);
+    }
+    root.left = left;
+    root.right = right;
+    root.update_size();
+    root
+}
+
+/// Joins two trees where every key in `left` is less than every key in `right`.
+fn join<K, V>(left: SplayLink<K, V>, right: SplayLink<K, V>) -> SplayLink<K, V> {
+    match left {
+        None => right,
+        Some(left) => {
+            let mut root = splay(left, |_| Ordering::Greater);
+            root.right = right;
+            root.update_size();
+            Some(root)
+        }
+    }
+}
+
+/// Self-adjusting binary search tree mapping ordered keys to values.
+pub struct SplayTree<K, V> {
+    root: SplayLink<K, V>,
+}
+
+impl<K: Ord, V> SplayTree<K, V> {
+    pub fn new() -> Self {
+        SplayTree { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        link_size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut level: Vec<&SplayNode<K, V>> = self.root.as_deref().into_iter().collect();
+        while !level.is_empty() {
+            height += 1;
+            level = level
+                .into_iter()
+                .flat_map(|node| node.left().into_iter().chain(node.right()))
+                .collect();
+        }
+        height
+    }
+
+    /// Looks up `key`, splaying it (or the last node on its search path) to the root.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        if self.splay(key) {
+            self.root.as_ref().map(|root| &root.value)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        if self.splay(key) {
+            self.root.as_mut().map(|root| &mut root.value)
+        } else {
+            None
+        }
+    }
+
+    /// Looks up `key` without restructuring the tree.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        bst::search(self.root.as_deref(), key).map(|node| &node.value)
+    }
+
+    pub fn contains_key(&mut self, key: &K) -> bool {
+        self.splay(key)
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced. The key
+    /// ends up at the root.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut node = Box::new(SplayNode {
+            key,
+            value,
+            size: 1,
+            left: None,
+            right: None,
+        });
+        if let Some(root) = self.root.take() {
+            let mut root = splay(root, |k| node.key.cmp(k));
+            match node.key.cmp(&root.key) {
+                Ordering::Equal => {
+                    let replaced = mem::replace(&mut root.value, node.value);
+                    self.root = Some(root);
+                    return Some(replaced);
+                }
+                Ordering::Less => {
+                    node.left = root.left.take();
+                    root.update_size();
+                    node.right = Some(root);
+                }
+                Ordering::Greater => {
+                    node.right = root.right.take();
+                    root.update_size();
+                    node.left = Some(root);
+                }
+            }
+            node.update_size();
+        }
+        self.root = Some(node);
+        None
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        if !self.splay(key) {
+            return None;
+        }
+        let SplayNode {
+            value, left, right, ..
+        } = *self.root.take().unwrap();
+        self.root = join(left, right);
+        Some(value)
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        bst::first(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        bst::last(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn iter(&self) -> Iter<'_, SplayNode<K, V>> {
+        Iter::new(self.root.as_deref(), &(..))
+    }
+
+    /// Entries whose keys lie within `range`, in ascending key order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, SplayNode<K, V>> {
+        Iter::new(self.root.as_deref(), &range)
+    }
+
+    /// Splits the tree into the entries with keys less than `key` and the rest.
+    pub fn split(mut self, key: &K) -> (Self, Self) {
+        let root = match self.root.take() {
+            Some(root) => root,
+            None => return (SplayTree::new(), SplayTree::new()),
+        };
+        let mut root = splay(root, |k| key.cmp(k));
+        let (left, right) = if root.key < *key {
+            let right = root.right.take();
+            root.update_size();
+            (Some(root), right)
+        } else {
+            let left = root.left.take();
+            root.update_size();
+            (left, Some(root))
+        };
+        (SplayTree { root: left }, SplayTree { root: right })
+    }
+
+    /// Concatenates two trees.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some key in `left` is not less than every key in `right`.
+    pub fn join(mut left: Self, mut right: Self) -> Self {
+        if let (Some((last, _)), Some((first, _))) = (left.last(), right.first()) {
+            assert!(last < first, "joined splay trees must not overlap");
+        }
+        SplayTree {
+            root: join(left.root.take(), right.root.take()),
+        }
+    }
+
+    /// Splays towards `key`, returning whether it ended up at the root.
+    fn splay(&mut self, key: &K) -> bool {
+        match self.root.take() {
+            Some(root) => {
+                let root = splay(root, |k| key.cmp(k));
+                let found = root.key == *key;
+                self.root = Some(root);
+                found
+            }
+            None => false,
+        }
+    }
+}
+
+impl<K: Ord, V> Default for SplayTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for SplayTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = SplayTree::new();
+        for (key, value) in iter {
+            tree.insert(key, value);
+        }
+        tree
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a SplayTree<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, SplayNode<K, V>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V> Drop for SplayTree<K, V> {
+    fn drop(&mut self) {
+        // Splay trees can degenerate into long paths, so unlink iteratively.
+        let mut stack: Vec<Box<SplayNode<K, V>>> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::bst::BstMap;
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn matches_reference_bst() {
+        let mut rng = StdRng::seed_from_u64(38);
+        let mut splay: SplayTree<i32, i32> = SplayTree::new();
+        let mut reference: BstMap<i32, i32> = BstMap::new();
+
+        for step in 0..2000 {
+            let key = rng.gen_range(0..300);
+            match rng.gen_range(0..3) {
+                0 => assert_eq!(splay.insert(key, step), reference.insert(key, step)),
+                1 => assert_eq!(splay.remove(&key), reference.remove(&key)),
+                _ => assert_eq!(splay.get(&key), reference.get(&key)),
+            }
+        }
+        assert_eq!(splay.len(), reference.len());
+        assert!(splay.iter().eq(reference.iter()));
+        assert!(splay.range(50..150).eq(reference.range(50..150)));
+    }
+
+    #[test]
+    fn access_moves_key_to_root() {
+        // Sequential inserts leave a path; a single access roughly halves its depth.
+        let mut tree: SplayTree<u32, u32> = (0..10_000).map(|x| (x, x)).collect();
+        assert_eq!(tree.height(), 10_000);
+        assert_eq!(tree.get(&0), Some(&0));
+        assert_eq!(tree.root.as_ref().unwrap().key, 0);
+        assert!(tree.height() <= 5_002);
+
+        assert_eq!(tree.get(&20_000), None);
+        assert_eq!(tree.root.as_ref().unwrap().key, 9_999);
+        assert_eq!(tree.peek(&4_321), Some(&4_321));
+        assert_eq!(tree.root.as_ref().unwrap().key, 9_999);
+    }
+
+    #[test]
+    fn split_and_join() {
+        let tree: SplayTree<u32, u32> = (0..100).map(|x| (x, x)).collect();
+        let (left, right) = tree.split(&40);
+        assert_eq!(left.len(), 40);
+        assert_eq!(right.len(), 60);
+        assert_eq!(left.last(), Some((&39, &39)));
+        assert_eq!(right.first(), Some((&40, &40)));
+
+        let joined = SplayTree::join(left, right);
+        assert_eq!(joined.len(), 100);
+        assert!(joined.iter().map(|(k, _)| *k).eq(0..100));
+    }
+}
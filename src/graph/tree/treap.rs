@@ -0,0 +1,702 @@
+//! Treaps: randomized binary search trees.
+//!
+//! Every node draws a random priority and the tree is kept in heap order on
+//! those priorities, which makes its shape that of a BST built from a random
+//! insertion order (Seidel and Aragon, 1996) and gives O(log n) expected depth.
+//! Everything is expressed through `split` and `merge`.
+//!
+//! [`ImplicitTreap`] uses the same shape for sequences: nodes are ordered by
+//! position rather than by a stored key, which allows inserting at an index,
+//! reversing ranges and aggregating ranges over a [`Monoid`].
+
+use super::bounds;
+use super::bst::{self, BinaryNode, Iter};
+use crate::math::algebra::Monoid;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::RangeBounds;
+
+type TreapLink<K, V> = Option<Box<TreapNode<K, V>>>;
+
+pub struct TreapNode<K, V> {
+    key: K,
+    value: V,
+    priority: u64,
+    /// Number of nodes in the subtree rooted here.
+    size: usize,
+    left: TreapLink<K, V>,
+    right: TreapLink<K, V>,
+}
+
+impl<K, V> BinaryNode for TreapNode<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+impl<K, V> TreapNode<K, V> {
+    fn update_size(&mut self) {
+        self.size = 1 + link_size(&self.left) + link_size(&self.right);
+    }
+}
+
+fn link_size<K, V>(link: &TreapLink<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+/// Splits `link` into the keys less than `key` and the rest.
+fn split<K: Ord, V>(link: TreapLink<K, V>, key: &K) -> (TreapLink<K, V>, TreapLink<K, V>) {
+    let mut node = match link {
+        Some(node) => node,
+        None => return (None, None),
+    };
+    if node.key < *key {
+        let (less, rest) = split(node.right.take(), key);
+        node.right = less;
+        node.update_size();
+        (Some(node), rest)
+    } else {
+        let (less, rest) = split(node.left.take(), key);
+        node.left = rest;
+        node.update_size();
+        (less, Some(node))
+    }
+}
+
+/// Joins two treaps where every key in `left` is less than every key in `right`.
+fn merge<K, V>(left: TreapLink<K, V>, right: TreapLink<K, V>) -> TreapLink<K, V> {
+    match (left, right) {
+        (None, right) => right,
+        (left, None) => left,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+                left.update_size();
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+                right.update_size();
+                Some(right)
+            }
+        }
+    }
+}
+
+/// Ordered map backed by a treap whose priorities come from a seedable RNG.
+pub struct Treap<K, V> {
+    root: TreapLink<K, V>,
+    rng: StdRng,
+}
+
+impl<K: Ord, V> Treap<K, V> {
+    pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+
+    /// Creates a treap whose shape is reproducible for a given insertion sequence.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: StdRng) -> Self {
+        Treap { root: None, rng }
+    }
+
+    pub fn len(&self) -> usize {
+        link_size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn height(&self) -> usize {
+        fn height<K, V>(link: &TreapLink<K, V>) -> usize {
+            link.as_ref()
+                .map_or(0, |node| 1 + height(&node.left).max(height(&node.right)))
+        }
+        height(&self.root)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        bst::search(self.root.as_deref(), key).map(|node| &node.value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let mut link = self.root.as_mut();
+        while let Some(node) = link {
+            link = match key.cmp(&node.key) {
+                Ordering::Less => node.left.as_mut(),
+                Ordering::Greater => node.right.as_mut(),
+                Ordering::Equal => return Some(&mut node.value),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(existing) = self.get_mut(&key) {
+            return Some(mem::replace(existing, value));
+        }
+        let node = Box::new(TreapNode {
+            priority: self.rng.gen(),
+            size: 1,
+            left: None,
+            right: None,
+            key,
+            value,
+        });
+        let (less, rest) = split(self.root.take(), &node.key);
+        self.root = merge(merge(less, Some(node)), rest);
+        None
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        fn remove_at<K: Ord, V>(link: &mut TreapLink<K, V>, key: &K) -> Option<V> {
+            let node = link.as_mut()?;
+            let removed = match key.cmp(&node.key) {
+                Ordering::Less => remove_at(&mut node.left, key),
+                Ordering::Greater => remove_at(&mut node.right, key),
+                Ordering::Equal => {
+                    let TreapNode {
+                        value, left, right, ..
+                    } = *link.take().unwrap();
+                    *link = merge(left, right);
+                    return Some(value);
+                }
+            };
+            node.update_size();
+            removed
+        }
+        remove_at(&mut self.root, key)
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        bst::first(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        bst::last(self.root.as_deref()).map(|node| (&node.key, &node.value))
+    }
+
+    /// Entry with the greatest key less than or equal to `key`.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        bst::floor(self.root.as_deref(), key).map(|node| (&node.key, &node.value))
+    }
+
+    /// Entry with the least key greater than or equal to `key`.
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        bst::ceiling(self.root.as_deref(), key).map(|node| (&node.key, &node.value))
+    }
+
+    pub fn iter(&self) -> Iter<'_, TreapNode<K, V>> {
+        Iter::new(self.root.as_deref(), &(..))
+    }
+
+    /// Entries whose keys lie within `range`, in ascending key order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, TreapNode<K, V>> {
+        Iter::new(self.root.as_deref(), &range)
+    }
+
+    /// Splits the treap into the entries with keys less than `key` and the rest.
+    ///
+    /// The right half continues with a fresh RNG seeded from this one.
+    pub fn split(mut self, key: &K) -> (Self, Self) {
+        let (less, rest) = split(self.root.take(), key);
+        let right = Treap {
+            root: rest,
+            rng: StdRng::seed_from_u64(self.rng.gen()),
+        };
+        self.root = less;
+        (self, right)
+    }
+
+    /// Joins two treaps, keeping the RNG of `left`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some key in `left` is not less than every key in `right`.
+    pub fn merge(mut left: Self, mut right: Self) -> Self {
+        if let (Some((last, _)), Some((first, _))) = (left.last(), right.first()) {
+            assert!(last < first, "merged treaps must not overlap");
+        }
+        left.root = merge(left.root.take(), right.root.take());
+        left
+    }
+}
+
+impl<K: Ord, V> Default for Treap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Treap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut treap = Treap::new();
+        for (key, value) in iter {
+            treap.insert(key, value);
+        }
+        treap
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a Treap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, TreapNode<K, V>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+type SeqLink<T> = Option<Box<SeqNode<T>>>;
+
+struct SeqNode<T> {
+    value: T,
+    priority: u64,
+    size: usize,
+    /// Aggregate of the subtree in sequence order, and in reverse order.
+    sum: T,
+    rev_sum: T,
+    /// The children of this node still have to be swapped and flipped.
+    reversed: bool,
+    left: SeqLink<T>,
+    right: SeqLink<T>,
+}
+
+impl<T> SeqNode<T> {
+    /// Reverses this subtree, deferring the work below this node.
+    fn flip(&mut self) {
+        self.reversed = !self.reversed;
+        mem::swap(&mut self.sum, &mut self.rev_sum);
+    }
+
+    fn push_down(&mut self) {
+        if self.reversed {
+            mem::swap(&mut self.left, &mut self.right);
+            for child in self.left.iter_mut().chain(self.right.iter_mut()) {
+                child.flip();
+            }
+            self.reversed = false;
+        }
+    }
+}
+
+fn seq_size<T>(link: &SeqLink<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+/// Sequence stored in a treap keyed by position, with range aggregates over `M`.
+///
+/// Reversing a range is O(log n) expected: it is recorded as a flag and only
+/// carried out lazily when a later operation descends through the node.
+pub struct ImplicitTreap<M: Monoid> {
+    root: SeqLink<M::Item>,
+    monoid: M,
+    rng: StdRng,
+}
+
+impl<M: Monoid> ImplicitTreap<M> {
+    pub fn new(monoid: M) -> Self {
+        Self::with_rng(monoid, StdRng::from_entropy())
+    }
+
+    pub fn with_seed(monoid: M, seed: u64) -> Self {
+        Self::with_rng(monoid, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(monoid: M, rng: StdRng) -> Self {
+        ImplicitTreap {
+            root: None,
+            monoid,
+            rng,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        seq_size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Element at `index`, or `None` past the end.
+    pub fn get(&self, mut index: usize) -> Option<&M::Item> {
+        let mut node = self.root.as_deref()?;
+        // Pending reversals on the path decide which child is really on the left.
+        let mut flipped = false;
+        loop {
+            flipped ^= node.reversed;
+            let (left, right) = if flipped {
+                (&node.right, &node.left)
+            } else {
+                (&node.left, &node.right)
+            };
+            let left_size = seq_size(left);
+            node = match index.cmp(&left_size) {
+                Ordering::Less => left.as_deref()?,
+                Ordering::Equal => return Some(&node.value),
+                Ordering::Greater => {
+                    index -= left_size + 1;
+                    right.as_deref()?
+                }
+            };
+        }
+    }
+
+    pub fn push(&mut self, value: M::Item) {
+        self.insert(self.len(), value);
+    }
+
+    /// Inserts `value` so that it ends up at position `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: M::Item) {
+        assert!(
+            index <= self.len(),
+            "insertion index {} is out of bounds",
+            index
+        );
+        let node = Box::new(SeqNode {
+            priority: self.rng.gen(),
+            size: 1,
+            sum: value.clone(),
+            rev_sum: value.clone(),
+            reversed: false,
+            left: None,
+            right: None,
+            value,
+        });
+        let root = self.root.take();
+        let (before, after) = self.split_link(root, index);
+        let before = self.merge_links(before, Some(node));
+        self.root = self.merge_links(before, after);
+    }
+
+    /// Removes and returns the element at `index`, or `None` past the end.
+    pub fn remove(&mut self, index: usize) -> Option<M::Item> {
+        if index >= self.len() {
+            return None;
+        }
+        let root = self.root.take();
+        let (before, rest) = self.split_link(root, index);
+        let (removed, after) = self.split_link(rest, 1);
+        self.root = self.merge_links(before, after);
+        removed.map(|node| node.value)
+    }
+
+    /// Reverses the elements within `range`.
+    pub fn reverse<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (start, end) = bounds(&range, self.len());
+        let root = self.root.take();
+        let (before, rest) = self.split_link(root, start);
+        let (mut middle, after) = self.split_link(rest, end - start);
+        if let Some(node) = middle.as_mut() {
+            node.flip();
+        }
+        let before = self.merge_links(before, middle);
+        self.root = self.merge_links(before, after);
+    }
+
+    /// Combines the elements within `range` in sequence order.
+    pub fn query<R: RangeBounds<usize>>(&mut self, range: R) -> M::Item {
+        let (start, end) = bounds(&range, self.len());
+        let root = self.root.take();
+        let (before, rest) = self.split_link(root, start);
+        let (middle, after) = self.split_link(rest, end - start);
+        let result = middle
+            .as_ref()
+            .map_or_else(|| self.monoid.identity(), |node| node.sum.clone());
+        let before = self.merge_links(before, middle);
+        self.root = self.merge_links(before, after);
+        result
+    }
+
+    /// Moves the elements from `at` onwards into a new sequence.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        M: Clone,
+    {
+        assert!(at <= self.len(), "split index {} is out of bounds", at);
+        let root = self.root.take();
+        let (before, after) = self.split_link(root, at);
+        self.root = before;
+        ImplicitTreap {
+            root: after,
+            monoid: self.monoid.clone(),
+            rng: StdRng::seed_from_u64(self.rng.gen()),
+        }
+    }
+
+    /// Moves all elements of `other` onto the end of this sequence.
+    pub fn append(&mut self, other: &mut Self) {
+        let root = self.root.take();
+        self.root = self.merge_links(root, other.root.take());
+    }
+
+    /// Elements in sequence order.
+    pub fn iter(&self) -> SeqIter<'_, M::Item> {
+        let mut iter = SeqIter { stack: Vec::new() };
+        iter.push_left(self.root.as_deref(), false);
+        iter
+    }
+
+    fn update(&self, node: &mut SeqNode<M::Item>) {
+        node.size = 1 + seq_size(&node.left) + seq_size(&node.right);
+        let identity = self.monoid.identity();
+        let (left_sum, left_rev) = node
+            .left
+            .as_ref()
+            .map_or((&identity, &identity), |l| (&l.sum, &l.rev_sum));
+        let (right_sum, right_rev) = node
+            .right
+            .as_ref()
+            .map_or((&identity, &identity), |r| (&r.sum, &r.rev_sum));
+        let sum = self
+            .monoid
+            .combine(&self.monoid.combine(left_sum, &node.value), right_sum);
+        let rev_sum = self
+            .monoid
+            .combine(&self.monoid.combine(right_rev, &node.value), left_rev);
+        node.sum = sum;
+        node.rev_sum = rev_sum;
+    }
+
+    /// Splits `link` into its first `count` elements and the rest.
+    fn split_link(
+        &self,
+        link: SeqLink<M::Item>,
+        count: usize,
+    ) -> (SeqLink<M::Item>, SeqLink<M::Item>) {
+        let mut node = match link {
+            Some(node) => node,
+            None => return (None, None),
+        };
+        node.push_down();
+        let left_size = seq_size(&node.left);
+        if count <= left_size {
+            let (before, after) = self.split_link(node.left.take(), count);
+            node.left = after;
+            self.update(&mut node);
+            (before, Some(node))
+        } else {
+            let (before, after) = self.split_link(node.right.take(), count - left_size - 1);
+            node.right = before;
+            self.update(&mut node);
+            (Some(node), after)
+        }
+    }
+
+    fn merge_links(&self, left: SeqLink<M::Item>, right: SeqLink<M::Item>) -> SeqLink<M::Item> {
+        match (left, right) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(mut left), Some(mut right)) => {
+                if left.priority > right.priority {
+                    left.push_down();
+                    left.right = self.merge_links(left.right.take(), Some(right));
+                    self.update(&mut left);
+                    Some(left)
+                } else {
+                    right.push_down();
+                    right.left = self.merge_links(Some(left), right.left.take());
+                    self.update(&mut right);
+                    Some(right)
+                }
+            }
+        }
+    }
+}
+
+impl<M: Monoid> Extend<M::Item> for ImplicitTreap<M> {
+    fn extend<I: IntoIterator<Item = M::Item>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+/// In-order iterator over an `ImplicitTreap`, resolving pending reversals on the fly.
+pub struct SeqIter<'a, T> {
+    /// Nodes still to be yielded, with whether their subtree is read reversed.
+    stack: Vec<(&'a SeqNode<T>, bool)>,
+}
+
+impl<'a, T> SeqIter<'a, T> {
+    fn push_left(&mut self, mut link: Option<&'a SeqNode<T>>, mut flipped: bool) {
+        while let Some(node) = link {
+            flipped ^= node.reversed;
+            self.stack.push((node, flipped));
+            link = if flipped {
+                node.right.as_deref()
+            } else {
+                node.left.as_deref()
+            };
+        }
+    }
+}
+
+impl<'a, T> Iterator for SeqIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let (node, flipped) = self.stack.pop()?;
+        let next = if flipped {
+            node.left.as_deref()
+        } else {
+            node.right.as_deref()
+        };
+        self.push_left(next, flipped);
+        Some(&node.value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::bst::BstMap;
+    use super::*;
+    use crate::math::algebra::Sum;
+
+    #[test]
+    fn matches_reference_bst() {
+        let mut rng = StdRng::seed_from_u64(37);
+        let mut treap: Treap<i32, i32> = Treap::with_seed(7);
+        let mut reference: BstMap<i32, i32> = BstMap::new();
+
+        for step in 0..2000 {
+            let key = rng.gen_range(0..300);
+            if rng.gen_bool(0.6) {
+                assert_eq!(treap.insert(key, step), reference.insert(key, step));
+            } else {
+                assert_eq!(treap.remove(&key), reference.remove(&key));
+            }
+        }
+        assert_eq!(treap.len(), reference.len());
+        assert!(treap.iter().eq(reference.iter()));
+        assert!(treap.range(50..150).eq(reference.range(50..150)));
+        assert_eq!(treap.floor(&151), reference.floor(&151));
+        assert!(treap.height() < 40);
+    }
+
+    #[test]
+    fn split_and_merge() {
+        let treap: Treap<u32, u32> = (0..100).map(|x| (x, x)).collect();
+        let (left, right) = treap.split(&40);
+        assert_eq!(left.len(), 40);
+        assert_eq!(right.len(), 60);
+        assert_eq!(left.last(), Some((&39, &39)));
+        assert_eq!(right.first(), Some((&40, &40)));
+
+        let joined = Treap::merge(left, right);
+        assert_eq!(joined.len(), 100);
+        assert!(joined.iter().map(|(k, _)| *k).eq(0..100));
+    }
+
+    #[test]
+    #[should_panic]
+    fn merge_rejects_overlap() {
+        let left: Treap<u32, ()> = (0..10).map(|x| (x, ())).collect();
+        let right: Treap<u32, ()> = (5..15).map(|x| (x, ())).collect();
+        Treap::merge(left, right);
+    }
+
+    #[test]
+    fn implicit_sequence_operations() {
+        let mut seq = ImplicitTreap::with_seed(Sum::new(), 37);
+        seq.extend(0..10i64);
+        seq.insert(3, 100);
+        assert_eq!(seq.len(), 11);
+        assert_eq!(seq.get(3), Some(&100));
+        assert_eq!(seq.query(..), 145);
+
+        seq.reverse(2..6);
+        assert_eq!(
+            seq.iter().cloned().collect::<Vec<i64>>(),
+            vec![0, 1, 4, 3, 100, 2, 5, 6, 7, 8, 9]
+        );
+        assert_eq!(seq.get(4), Some(&100));
+        assert_eq!(seq.query(1..4), 8);
+        assert_eq!(seq.remove(4), Some(100));
+
+        let mut tail = seq.split_off(5);
+        assert_eq!(
+            tail.iter().cloned().collect::<Vec<i64>>(),
+            vec![5, 6, 7, 8, 9]
+        );
+        tail.append(&mut seq);
+        assert_eq!(
+            tail.iter().cloned().collect::<Vec<i64>>(),
+            vec![5, 6, 7, 8, 9, 0, 1, 4, 3, 2]
+        );
+    }
+
+    /// Concatenation of strings is not commutative, so aggregates over
+    /// reversed ranges must be read in the reversed order.
+    #[derive(Clone)]
+    struct Concat;
+
+    impl Monoid for Concat {
+        type Item = String;
+
+        fn identity(&self) -> String {
+            String::new()
+        }
+
+        fn combine(&self, a: &String, b: &String) -> String {
+            format!("{}{}", a, b)
+        }
+    }
+
+    #[test]
+    fn reversal_matches_vec() {
+        let mut rng = StdRng::seed_from_u64(370);
+        let mut seq = ImplicitTreap::with_seed(Concat, 1);
+        let mut reference: Vec<String> = Vec::new();
+        for c in "abcdefghijklmnopqrstuvwxyz".chars() {
+            seq.push(c.to_string());
+            reference.push(c.to_string());
+        }
+        for _ in 0..200 {
+            let start = rng.gen_range(0..reference.len());
+            let end = rng.gen_range(start..=reference.len());
+            seq.reverse(start..end);
+            reference[start..end].reverse();
+
+            let start = rng.gen_range(0..reference.len());
+            let end = rng.gen_range(start..=reference.len());
+            assert_eq!(seq.query(start..end), reference[start..end].concat());
+        }
+        assert!(seq.iter().eq(reference.iter()));
+    }
+}
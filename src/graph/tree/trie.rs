@@ -0,0 +1,548 @@
+//! Prefix trees over byte-string keys.
+//!
+//! [`Trie`] stores one node per key byte, while [`RadixTree`] compresses
+//! chains of single-child nodes into one edge labelled with the whole byte run.
+//! Both keep children in byte order, so every enumeration is lexicographic.
+//! Keys are anything that can be viewed as bytes (`&str`, `String`, `&[u8]`,
+//! ...) and are handed back as `Vec<u8>`.
+
+use std::collections::btree_map::{self, BTreeMap};
+use std::iter::FromIterator;
+
+struct TrieNode<V> {
+    value: Option<V>,
+    children: BTreeMap<u8, TrieNode<V>>,
+}
+
+impl<V> TrieNode<V> {
+    fn new() -> Self {
+        TrieNode {
+            value: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+/// Map from byte strings to values with one node per key byte.
+pub struct Trie<V> {
+    root: TrieNode<V>,
+    len: usize,
+}
+
+impl<V> Trie<V> {
+    pub fn new() -> Self {
+        Trie {
+            root: TrieNode::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced.
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, value: V) -> Option<V> {
+        let mut node = &mut self.root;
+        for byte in key.as_ref() {
+            node = node.children.entry(*byte).or_insert_with(TrieNode::new);
+        }
+        let replaced = node.value.replace(value);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&V> {
+        self.node(key.as_ref())?.value.as_ref()
+    }
+
+    pub fn get_mut<K: AsRef<[u8]>>(&mut self, key: K) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        for byte in key.as_ref() {
+            node = node.children.get_mut(byte)?;
+        }
+        node.value.as_mut()
+    }
+
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes `key`, pruning any nodes that no longer lead to a value.
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<V> {
+        fn remove_at<V>(node: &mut TrieNode<V>, key: &[u8]) -> Option<V> {
+            let (first, rest) = match key.split_first() {
+                Some(split) => split,
+                None => return node.value.take(),
+            };
+            let child = node.children.get_mut(first)?;
+            let removed = remove_at(child, rest)?;
+            if child.value.is_none() && child.children.is_empty() {
+                node.children.remove(first);
+            }
+            Some(removed)
+        }
+
+        let removed = remove_at(&mut self.root, key.as_ref());
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Value of the longest key that is a prefix of `key`, along with that
+    /// key's length.
+    pub fn longest_prefix<K: AsRef<[u8]>>(&self, key: K) -> Option<(usize, &V)> {
+        let mut node = &self.root;
+        let mut best = node.value.as_ref().map(|value| (0, value));
+        for (depth, byte) in key.as_ref().iter().enumerate() {
+            node = match node.children.get(byte) {
+                Some(child) => child,
+                None => break,
+            };
+            if let Some(value) = node.value.as_ref() {
+                best = Some((depth + 1, value));
+            }
+        }
+        best
+    }
+
+    /// All entries in lexicographic key order.
+    pub fn iter(&self) -> TrieIter<'_, V> {
+        self.prefix_iter([])
+    }
+
+    /// Entries whose keys start with `prefix`, in lexicographic key order.
+    pub fn prefix_iter<K: AsRef<[u8]>>(&self, prefix: K) -> TrieIter<'_, V> {
+        let prefix = prefix.as_ref();
+        match self.node(prefix) {
+            Some(node) => TrieIter {
+                path: prefix.to_vec(),
+                stack: vec![node.children.iter()],
+                pending: node.value.as_ref(),
+            },
+            None => TrieIter {
+                path: Vec::new(),
+                stack: Vec::new(),
+                pending: None,
+            },
+        }
+    }
+
+    fn node(&self, key: &[u8]) -> Option<&TrieNode<V>> {
+        let mut node = &self.root;
+        for byte in key {
+            node = node.children.get(byte)?;
+        }
+        Some(node)
+    }
+}
+
+impl<V> Default for Trie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: AsRef<[u8]>, V> FromIterator<(K, V)> for Trie<V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut trie = Trie::new();
+        for (key, value) in iter {
+            trie.insert(key, value);
+        }
+        trie
+    }
+}
+
+/// Depth-first, lexicographically ordered iterator over (part of) a `Trie`.
+pub struct TrieIter<'a, V> {
+    /// Key of the node whose children the top of `stack` walks.
+    path: Vec<u8>,
+    stack: Vec<btree_map::Iter<'a, u8, TrieNode<V>>>,
+    /// Value of the starting node, yielded before any of its descendants.
+    pending: Option<&'a V>,
+}
+
+impl<'a, V> Iterator for TrieIter<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(value) = self.pending.take() {
+            return Some((self.path.clone(), value));
+        }
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some((byte, child)) => {
+                    self.path.push(*byte);
+                    self.stack.push(child.children.iter());
+                    if let Some(value) = child.value.as_ref() {
+                        return Some((self.path.clone(), value));
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    self.path.pop();
+                }
+            }
+        }
+    }
+}
+
+struct RadixNode<V> {
+    /// Bytes on the edge from the parent; empty only for the root.
+    label: Vec<u8>,
+    value: Option<V>,
+    /// Children keyed by the first byte of their label.
+    children: BTreeMap<u8, RadixNode<V>>,
+}
+
+impl<V> RadixNode<V> {
+    fn new(label: Vec<u8>, value: Option<V>) -> Self {
+        RadixNode {
+            label,
+            value,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// Map from byte strings to values in a path-compressed trie.
+///
+/// Every node other than the root either holds a value or has at least two
+/// children, so the tree has at most `2n` nodes regardless of key length.
+pub struct RadixTree<V> {
+    root: RadixNode<V>,
+    len: usize,
+}
+
+impl<V> RadixTree<V> {
+    pub fn new() -> Self {
+        RadixTree {
+            root: RadixNode::new(Vec::new(), None),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced.
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, value: V) -> Option<V> {
+        let mut node = &mut self.root;
+        let mut rest = key.as_ref();
+        while let Some(first) = rest.first() {
+            let child = match node.children.remove(first) {
+                Some(child) => child,
+                None => {
+                    node.children
+                        .insert(*first, RadixNode::new(rest.to_vec(), Some(value)));
+                    self.len += 1;
+                    return None;
+                }
+            };
+            let common = common_prefix_len(&child.label, rest);
+            let child = if common < child.label.len() {
+                // The key leaves this edge part-way along: split it.
+                let mut child = child;
+                let mut middle = RadixNode::new(child.label[..common].to_vec(), None);
+                child.label.drain(..common);
+                middle.children.insert(child.label[0], child);
+                middle
+            } else {
+                child
+            };
+            rest = &rest[common..];
+            node = node.children.entry(*first).or_insert(child);
+        }
+        let replaced = node.value.replace(value);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&V> {
+        let mut node = &self.root;
+        let mut rest = key.as_ref();
+        while let Some(first) = rest.first() {
+            node = node.children.get(first)?;
+            rest = rest.strip_prefix(node.label.as_slice())?;
+        }
+        node.value.as_ref()
+    }
+
+    pub fn get_mut<K: AsRef<[u8]>>(&mut self, key: K) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        let mut rest = key.as_ref();
+        while let Some(first) = rest.first() {
+            node = node.children.get_mut(first)?;
+            rest = rest.strip_prefix(node.label.as_slice())?;
+        }
+        node.value.as_mut()
+    }
+
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes `key`, merging nodes left with a single child back into their
+    /// parent edge.
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<V> {
+        fn remove_at<V>(node: &mut RadixNode<V>, key: &[u8]) -> Option<V> {
+            let first = match key.first() {
+                Some(first) => first,
+                None => return node.value.take(),
+            };
+            let child = node.children.get_mut(first)?;
+            let rest = key.strip_prefix(child.label.as_slice())?;
+            let removed = remove_at(child, rest)?;
+            if child.value.is_none() {
+                match child.children.len() {
+                    0 => {
+                        node.children.remove(first);
+                    }
+                    1 => {
+                        let (_, grandchild) = child.children.pop_first().unwrap();
+                        child.label.extend(grandchild.label);
+                        child.value = grandchild.value;
+                        child.children = grandchild.children;
+                    }
+                    _ => {}
+                }
+            }
+            Some(removed)
+        }
+
+        let removed = remove_at(&mut self.root, key.as_ref());
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Value of the longest key that is a prefix of `key`, along with that
+    /// key's length.
+    pub fn longest_prefix<K: AsRef<[u8]>>(&self, key: K) -> Option<(usize, &V)> {
+        let key = key.as_ref();
+        let mut node = &self.root;
+        let mut depth = 0;
+        let mut best = node.value.as_ref().map(|value| (0, value));
+        while let Some(first) = key.get(depth) {
+            node = match node.children.get(first) {
+                Some(child) if key[depth..].starts_with(&child.label) => child,
+                _ => break,
+            };
+            depth += node.label.len();
+            if let Some(value) = node.value.as_ref() {
+                best = Some((depth, value));
+            }
+        }
+        best
+    }
+
+    /// All entries in lexicographic key order.
+    pub fn iter(&self) -> RadixIter<'_, V> {
+        self.prefix_iter([])
+    }
+
+    /// Entries whose keys start with `prefix`, in lexicographic key order.
+    pub fn prefix_iter<K: AsRef<[u8]>>(&self, prefix: K) -> RadixIter<'_, V> {
+        let prefix = prefix.as_ref();
+        let mut node = &self.root;
+        let mut path = Vec::new();
+        while path.len() < prefix.len() {
+            let rest = &prefix[path.len()..];
+            match node.children.get(&rest[0]) {
+                // The prefix ends on this edge or covers all of it.
+                Some(child) if child.label.starts_with(rest) || rest.starts_with(&child.label) => {
+                    path.extend_from_slice(&child.label);
+                    node = child;
+                }
+                _ => {
+                    return RadixIter {
+                        path: Vec::new(),
+                        stack: Vec::new(),
+                        pending: None,
+                    }
+                }
+            }
+        }
+        RadixIter {
+            path,
+            stack: vec![(node.children.iter(), 0)],
+            pending: node.value.as_ref(),
+        }
+    }
+}
+
+impl<V> Default for RadixTree<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: AsRef<[u8]>, V> FromIterator<(K, V)> for RadixTree<V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = RadixTree::new();
+        for (key, value) in iter {
+            tree.insert(key, value);
+        }
+        tree
+    }
+}
+
+/// Depth-first, lexicographically ordered iterator over (part of) a `RadixTree`.
+pub struct RadixIter<'a, V> {
+    /// Key of the node whose children the top of `stack` walks.
+    path: Vec<u8>,
+    /// Child iterators along with the label length of the node they belong to.
+    stack: Vec<(btree_map::Iter<'a, u8, RadixNode<V>>, usize)>,
+    /// Value of the starting node, yielded before any of its descendants.
+    pending: Option<&'a V>,
+}
+
+impl<'a, V> Iterator for RadixIter<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(value) = self.pending.take() {
+            return Some((self.path.clone(), value));
+        }
+        loop {
+            let (children, _) = self.stack.last_mut()?;
+            match children.next() {
+                Some((_, child)) => {
+                    self.path.extend_from_slice(&child.label);
+                    self.stack.push((child.children.iter(), child.label.len()));
+                    if let Some(value) = child.value.as_ref() {
+                        return Some((self.path.clone(), value));
+                    }
+                }
+                None => {
+                    let (_, label_len) = self.stack.pop().unwrap();
+                    self.path.truncate(self.path.len() - label_len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::BTreeMap as StdBTreeMap;
+
+    fn words() -> Vec<&'static str> {
+        vec![
+            "romane",
+            "romanus",
+            "romulus",
+            "rubens",
+            "ruber",
+            "rubicon",
+            "rubicundus",
+            "rom",
+        ]
+    }
+
+    #[test]
+    fn trie_prefix_queries() {
+        let mut trie: Trie<usize> = words().into_iter().zip(0..).collect();
+        assert_eq!(trie.len(), 8);
+        assert_eq!(trie.get("rubens"), Some(&3));
+        assert_eq!(trie.get("ruben"), None);
+
+        let completions: Vec<Vec<u8>> = trie.prefix_iter("rom").map(|(key, _)| key).collect();
+        assert_eq!(
+            completions,
+            vec![
+                b"rom".to_vec(),
+                b"romane".to_vec(),
+                b"romanus".to_vec(),
+                b"romulus".to_vec()
+            ]
+        );
+        assert_eq!(trie.prefix_iter("x").count(), 0);
+
+        assert_eq!(trie.longest_prefix("romanesque"), Some((6, &0)));
+        assert_eq!(trie.longest_prefix("romance"), Some((3, &7)));
+        assert_eq!(trie.longest_prefix("rub"), None);
+
+        assert_eq!(trie.remove("rom"), Some(7));
+        assert_eq!(trie.remove("rom"), None);
+        assert_eq!(trie.longest_prefix("romance"), None);
+        assert_eq!(trie.prefix_iter(b"rom").count(), 3);
+    }
+
+    #[test]
+    fn radix_tree_compresses_edges() {
+        let mut tree: RadixTree<usize> = words().into_iter().zip(0..).collect();
+        assert_eq!(tree.len(), 8);
+        assert_eq!(tree.root.children.len(), 1);
+        assert_eq!(tree.root.children[&b'r'].label, b"r".to_vec());
+
+        assert_eq!(tree.get("rubicon"), Some(&5));
+        assert_eq!(tree.get("rubi"), None);
+        let completions: Vec<Vec<u8>> = tree.prefix_iter("rubi").map(|(key, _)| key).collect();
+        assert_eq!(
+            completions,
+            vec![b"rubicon".to_vec(), b"rubicundus".to_vec()]
+        );
+        assert_eq!(tree.longest_prefix("rubensstraat"), Some((6, &3)));
+
+        assert_eq!(tree.remove("rubicon"), Some(5));
+        let rub = &tree.root.children[&b'r'].children[&b'u'];
+        assert_eq!(rub.children[&b'i'].label, b"icundus".to_vec());
+    }
+
+    #[test]
+    fn matches_reference_map() {
+        let mut rng = StdRng::seed_from_u64(41);
+        let mut trie = Trie::new();
+        let mut radix = RadixTree::new();
+        let mut reference: StdBTreeMap<Vec<u8>, u32> = StdBTreeMap::new();
+
+        for step in 0..3000 {
+            let len = rng.gen_range(0..6);
+            let key: Vec<u8> = (0..len).map(|_| rng.gen_range(b'a'..b'd')).collect();
+            if rng.gen_bool(0.6) {
+                let expected = reference.insert(key.clone(), step);
+                assert_eq!(trie.insert(&key, step), expected);
+                assert_eq!(radix.insert(&key, step), expected);
+            } else {
+                let expected = reference.remove(&key);
+                assert_eq!(trie.remove(&key), expected);
+                assert_eq!(radix.remove(&key), expected);
+            }
+        }
+        let expected: Vec<(Vec<u8>, &u32)> =
+            reference.iter().map(|(k, v)| (k.clone(), v)).collect();
+        assert_eq!(trie.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(radix.iter().collect::<Vec<_>>(), expected);
+
+        let with_prefix: Vec<(Vec<u8>, &u32)> = expected
+            .iter()
+            .filter(|(k, _)| k.starts_with(b"ab"))
+            .cloned()
+            .collect();
+        assert_eq!(trie.prefix_iter("ab").collect::<Vec<_>>(), with_prefix);
+        assert_eq!(radix.prefix_iter("ab").collect::<Vec<_>>(), with_prefix);
+    }
+}
@@ -2,5 +2,6 @@ pub mod algorithms;
 pub mod data_structures;
 pub mod graph;
 pub mod math;
+pub mod strings;
 
 extern crate num_traits;
@@ -1,3 +1,4 @@
+pub mod algebra;
 pub mod basic;
 pub mod linalg;
 pub mod units;
@@ -0,0 +1,319 @@
+//! Algebraic structures used to parameterise range-aggregate data structures.
+
+use super::basic::GCD;
+use num_traits::{Bounded, Num, NumCast, Zero};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// An associative operation with an identity element.
+///
+/// Methods take `&self` so that a monoid can carry state, such as a closure
+/// or a modulus, alongside its operation.
+pub trait Monoid {
+    type Item: Clone;
+
+    fn identity(&self) -> Self::Item;
+    fn combine(&self, a: &Self::Item, b: &Self::Item) -> Self::Item;
+}
+
+/// Updates that act on the values of monoid `M`, as used for lazy range updates.
+///
+/// Applying an update to the aggregate of a range must give the aggregate of
+/// the updated elements, which is why `apply` is told how many elements the
+/// aggregate covers.
+pub trait Action<M: Monoid> {
+    type Update: Clone;
+
+    /// The update that leaves every value unchanged.
+    fn identity(&self) -> Self::Update;
+    fn apply(&self, update: &Self::Update, value: &M::Item, len: usize) -> M::Item;
+    /// The single update equivalent to applying `first` and then `second`.
+    fn compose(&self, first: &Self::Update, second: &Self::Update) -> Self::Update;
+}
+
+/// Declares a zero-sized marker generic over `T` whose `Clone`, `Copy`,
+/// `Default` and `Debug` impls do not require the same of `T`, as deriving
+/// them would.
+macro_rules! marker {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        pub struct $name<T>(PhantomData<T>);
+
+        impl<T> $name<T> {
+            pub fn new() -> Self {
+                $name(PhantomData)
+            }
+        }
+
+        impl<T> Clone for $name<T> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<T> Copy for $name<T> {}
+
+        impl<T> Default for $name<T> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<T> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(stringify!($name))
+            }
+        }
+    };
+}
+
+marker! {
+    /// Addition, with zero as the identity.
+    Sum
+}
+
+marker! {
+    /// Minimum, with the largest value of `T` as the identity.
+    Min
+}
+
+marker! {
+    /// Maximum, with the smallest value of `T` as the identity.
+    Max
+}
+
+marker! {
+    /// Greatest common divisor, with zero as the identity.
+    Gcd
+}
+
+marker! {
+    /// Range update adding a constant to every element.
+    Add
+}
+
+marker! {
+    /// Range update overwriting every element; `None` is the identity.
+    Assign
+}
+
+impl<T: Zero + Clone> Monoid for Sum<T> {
+    type Item = T;
+
+    fn identity(&self) -> T {
+        T::zero()
+    }
+
+    fn combine(&self, a: &T, b: &T) -> T {
+        a.clone() + b.clone()
+    }
+}
+
+impl<T: Bounded + PartialOrd + Clone> Monoid for Min<T> {
+    type Item = T;
+
+    fn identity(&self) -> T {
+        T::max_value()
+    }
+
+    fn combine(&self, a: &T, b: &T) -> T {
+        if b < a {
+            b.clone()
+        } else {
+            a.clone()
+        }
+    }
+}
+
+impl<T: Bounded + PartialOrd + Clone> Monoid for Max<T> {
+    type Item = T;
+
+    fn identity(&self) -> T {
+        T::min_value()
+    }
+
+    fn combine(&self, a: &T, b: &T) -> T {
+        if b > a {
+            b.clone()
+        } else {
+            a.clone()
+        }
+    }
+}
+
+impl<T: GCD> Monoid for Gcd<T> {
+    type Item = T;
+
+    fn identity(&self) -> T {
+        T::zero()
+    }
+
+    fn combine(&self, a: &T, b: &T) -> T {
+        a.gcd_euclidean(*b)
+    }
+}
+
+/// Monoid built from a user-supplied identity and combining closure.
+#[derive(Clone)]
+pub struct FnMonoid<T, F> {
+    identity: T,
+    combine: F,
+}
+
+impl<T: Clone, F: Fn(&T, &T) -> T> FnMonoid<T, F> {
+    /// `combine` must be associative and `identity` neutral with respect to it.
+    pub fn new(identity: T, combine: F) -> Self {
+        FnMonoid { identity, combine }
+    }
+}
+
+impl<T: Clone, F: Fn(&T, &T) -> T> Monoid for FnMonoid<T, F> {
+    type Item = T;
+
+    fn identity(&self) -> T {
+        self.identity.clone()
+    }
+
+    fn combine(&self, a: &T, b: &T) -> T {
+        (self.combine)(a, b)
+    }
+}
+
+fn times<T: Num + NumCast + Copy>(value: T, len: usize) -> T {
+    value * T::from(len).expect("range length does not fit the element type")
+}
+
+impl<T: Num + NumCast + Copy> Action<Sum<T>> for Add<T> {
+    type Update = T;
+
+    fn identity(&self) -> T {
+        T::zero()
+    }
+
+    fn apply(&self, update: &T, value: &T, len: usize) -> T {
+        *value + times(*update, len)
+    }
+
+    fn compose(&self, first: &T, second: &T) -> T {
+        *first + *second
+    }
+}
+
+impl<T: Num + Bounded + PartialOrd + Copy> Action<Min<T>> for Add<T> {
+    type Update = T;
+
+    fn identity(&self) -> T {
+        T::zero()
+    }
+
+    /// The identity `T::max_value()` stands for an empty range and is left
+    /// unchanged rather than overflowing.
+    fn apply(&self, update: &T, value: &T, _len: usize) -> T {
+        if *value == T::max_value() {
+            return *value;
+        }
+        *value + *update
+    }
+
+    fn compose(&self, first: &T, second: &T) -> T {
+        *first + *second
+    }
+}
+
+impl<T: Num + Bounded + PartialOrd + Copy> Action<Max<T>> for Add<T> {
+    type Update = T;
+
+    fn identity(&self) -> T {
+        T::zero()
+    }
+
+    /// The identity `T::min_value()` is left unchanged, as for `Min`.
+    fn apply(&self, update: &T, value: &T, _len: usize) -> T {
+        if *value == T::min_value() {
+            return *value;
+        }
+        *value + *update
+    }
+
+    fn compose(&self, first: &T, second: &T) -> T {
+        *first + *second
+    }
+}
+
+impl<T: Num + NumCast + Copy> Action<Sum<T>> for Assign<T> {
+    type Update = Option<T>;
+
+    fn identity(&self) -> Option<T> {
+        None
+    }
+
+    fn apply(&self, update: &Option<T>, value: &T, len: usize) -> T {
+        update.map_or(*value, |v| times(v, len))
+    }
+
+    fn compose(&self, first: &Option<T>, second: &Option<T>) -> Option<T> {
+        second.or(*first)
+    }
+}
+
+impl<T: Bounded + PartialOrd + Copy> Action<Min<T>> for Assign<T> {
+    type Update = Option<T>;
+
+    fn identity(&self) -> Option<T> {
+        None
+    }
+
+    fn apply(&self, update: &Option<T>, value: &T, _len: usize) -> T {
+        update.unwrap_or(*value)
+    }
+
+    fn compose(&self, first: &Option<T>, second: &Option<T>) -> Option<T> {
+        second.or(*first)
+    }
+}
+
+impl<T: Bounded + PartialOrd + Copy> Action<Max<T>> for Assign<T> {
+    type Update = Option<T>;
+
+    fn identity(&self) -> Option<T> {
+        None
+    }
+
+    fn apply(&self, update: &Option<T>, value: &T, _len: usize) -> T {
+        update.unwrap_or(*value)
+    }
+
+    fn compose(&self, first: &Option<T>, second: &Option<T>) -> Option<T> {
+        second.or(*first)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn predefined_monoids() {
+        let values = [12i64, -18, 30];
+        let fold = |m: &dyn Monoid<Item = i64>| {
+            values
+                .iter()
+                .fold(m.identity(), |acc, v| m.combine(&acc, v))
+        };
+        assert_eq!(fold(&Sum::new()), 24);
+        assert_eq!(fold(&Min::new()), -18);
+        assert_eq!(fold(&Max::new()), 30);
+        assert_eq!(fold(&Gcd::new()), 6);
+        assert_eq!(fold(&FnMonoid::new(1, |a: &i64, b: &i64| a * b)), -6480);
+    }
+
+    #[test]
+    fn markers_copy_without_bounds() {
+        struct Opaque;
+        fn copy<T: Copy + Default + fmt::Debug>(_: T) {}
+        copy(Sum::<Opaque>::new());
+        copy(Min::<Opaque>::default());
+        copy(Assign::<Opaque>::new());
+        assert_eq!(format!("{:?}", Max::<Opaque>::new()), "Max");
+    }
+}
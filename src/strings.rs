@@ -0,0 +1 @@
+pub mod suffix_array;
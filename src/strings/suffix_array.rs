@@ -0,0 +1,228 @@
+//! Suffix arrays with longest-common-prefix arrays.
+//!
+//! The suffix array lists the starting positions of all suffixes of a text in
+//! lexicographic order. It is built by prefix doubling (Manber and Myers,
+//! 1993): suffixes are ranked by their first `k` bytes, then by their first
+//! `2k` bytes using the pair of ranks at `i` and `i + k`, with each round a
+//! counting sort. The LCP array follows from it in linear time (Kasai et al.,
+//! 2001).
+
+/// Suffix and LCP arrays of a byte string.
+pub struct SuffixArray {
+    text: Vec<u8>,
+    suffixes: Vec<usize>,
+    /// `lcp[i]` is the length of the longest common prefix of the suffixes at
+    /// `suffixes[i - 1]` and `suffixes[i]`; `lcp[0]` is 0.
+    lcp: Vec<usize>,
+}
+
+impl SuffixArray {
+    /// Builds both arrays in O(n log n).
+    pub fn new<T: AsRef<[u8]>>(text: T) -> Self {
+        let text = text.as_ref().to_vec();
+        let suffixes = build(&text);
+        let lcp = kasai(&text, &suffixes);
+        SuffixArray {
+            text,
+            suffixes,
+            lcp,
+        }
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Starting positions of the suffixes in lexicographic order.
+    pub fn suffixes(&self) -> &[usize] {
+        &self.suffixes
+    }
+
+    pub fn lcp(&self) -> &[usize] {
+        &self.lcp
+    }
+
+    /// Starting positions of every occurrence of `pattern`, in the order of
+    /// the suffixes they begin. Takes O(m log n) for a pattern of length m.
+    pub fn occurrences<P: AsRef<[u8]>>(&self, pattern: P) -> &[usize] {
+        let pattern = pattern.as_ref();
+        let prefix = |start: usize| &self.text[start..self.len().min(start + pattern.len())];
+        let lower = self.suffixes.partition_point(|s| prefix(*s) < pattern);
+        let upper = self.suffixes.partition_point(|s| prefix(*s) <= pattern);
+        &self.suffixes[lower..upper]
+    }
+
+    pub fn contains<P: AsRef<[u8]>>(&self, pattern: P) -> bool {
+        !self.occurrences(pattern).is_empty()
+    }
+
+    /// Number of distinct non-empty substrings: every suffix contributes its
+    /// prefixes except the ones it shares with the suffix before it.
+    pub fn distinct_substrings(&self) -> usize {
+        let n = self.len();
+        n * (n + 1) / 2 - self.lcp.iter().sum::<usize>()
+    }
+
+    /// The longest substring occurring at least twice (possibly overlapping),
+    /// or `None` if no byte repeats. Ties go to the lexicographically smallest.
+    pub fn longest_repeated_substring(&self) -> Option<&[u8]> {
+        let (i, len) =
+            self.lcp.iter().enumerate().fold(
+                (0, 0),
+                |best, (i, len)| if *len > best.1 { (i, *len) } else { best },
+            );
+        if len == 0 {
+            return None;
+        }
+        let start = self.suffixes[i];
+        Some(&self.text[start..start + len])
+    }
+}
+
+/// Suffix array of `text` by prefix doubling.
+fn build(text: &[u8]) -> Vec<usize> {
+    let n = text.len();
+    let mut suffixes: Vec<usize> = (0..n).collect();
+    suffixes.sort_by_key(|i| text[*i]);
+    if n <= 1 {
+        return suffixes;
+    }
+    let mut rank: Vec<usize> = text.iter().map(|b| *b as usize).collect();
+    let mut next_rank = vec![0; n];
+    let mut by_second = Vec::with_capacity(n);
+    let mut counts = Vec::new();
+
+    let mut k = 1;
+    loop {
+        // Order by the rank at `i + k`: suffixes shorter than `k + 1` come
+        // first, then the rest follow the current order of `i + k`.
+        by_second.clear();
+        by_second.extend(n.saturating_sub(k)..n);
+        by_second.extend(suffixes.iter().filter(|s| **s >= k).map(|s| s - k));
+
+        // Stable counting sort by the rank at `i`.
+        counts.clear();
+        counts.resize(rank.iter().max().unwrap() + 2, 0);
+        for i in by_second.iter() {
+            counts[rank[*i] + 1] += 1;
+        }
+        for r in 1..counts.len() {
+            counts[r] += counts[r - 1];
+        }
+        for i in by_second.iter() {
+            suffixes[counts[rank[*i]]] = *i;
+            counts[rank[*i]] += 1;
+        }
+
+        let key = |i: usize| (rank[i], rank.get(i + k).map(|r| r + 1).unwrap_or(0));
+        next_rank[suffixes[0]] = 0;
+        for w in 1..n {
+            let changed = key(suffixes[w - 1]) != key(suffixes[w]);
+            next_rank[suffixes[w]] = next_rank[suffixes[w - 1]] + usize::from(changed);
+        }
+        std::mem::swap(&mut rank, &mut next_rank);
+        if rank[suffixes[n - 1]] == n - 1 {
+            break;
+        }
+        k *= 2;
+    }
+    suffixes
+}
+
+/// LCP array of `text` given its suffix array, in O(n).
+fn kasai(text: &[u8], suffixes: &[usize]) -> Vec<usize> {
+    let n = text.len();
+    let mut rank = vec![0; n];
+    for (r, s) in suffixes.iter().enumerate() {
+        rank[*s] = r;
+    }
+    let mut lcp = vec![0; n];
+    // The common prefix shrinks by at most one from each suffix to the next.
+    let mut h: usize = 0;
+    for i in 0..n {
+        if rank[i] == 0 {
+            h = 0;
+            continue;
+        }
+        let j = suffixes[rank[i] - 1];
+        while i + h < n && j + h < n && text[i + h] == text[j + h] {
+            h += 1;
+        }
+        lcp[rank[i]] = h;
+        h = h.saturating_sub(1);
+    }
+    lcp
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    #[test]
+    fn banana() {
+        let sa = SuffixArray::new("banana");
+        assert_eq!(sa.suffixes(), &[5, 3, 1, 0, 4, 2]);
+        assert_eq!(sa.lcp(), &[0, 1, 3, 0, 0, 2]);
+        assert_eq!(sa.occurrences("ana"), &[3, 1]);
+        assert!(sa.contains("nan"));
+        assert!(!sa.contains("nab"));
+        assert_eq!(sa.occurrences("").len(), 6);
+        assert_eq!(sa.distinct_substrings(), 15);
+        assert_eq!(sa.longest_repeated_substring(), Some(&b"ana"[..]));
+
+        assert_eq!(SuffixArray::new("abc").longest_repeated_substring(), None);
+        assert!(SuffixArray::new("").suffixes().is_empty());
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(49);
+        for _ in 0..50 {
+            let len = rng.gen_range(1..80);
+            let text: Vec<u8> = (0..len).map(|_| b"abc"[rng.gen_range(0..3)]).collect();
+            let sa = SuffixArray::new(&text);
+
+            let mut expected: Vec<usize> = (0..len).collect();
+            expected.sort_by_key(|i| &text[*i..]);
+            assert_eq!(sa.suffixes(), &expected[..]);
+            for w in 1..len {
+                let (a, b) = (&text[expected[w - 1]..], &text[expected[w]..]);
+                assert_eq!(
+                    sa.lcp()[w],
+                    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+                );
+            }
+
+            let substrings: HashSet<&[u8]> = (0..len)
+                .flat_map(|i| (i + 1..=len).map(move |j| (i, j)))
+                .map(|(i, j)| &text[i..j])
+                .collect();
+            assert_eq!(sa.distinct_substrings(), substrings.len());
+
+            let start = rng.gen_range(0..len);
+            let pattern = &text[start..len.min(start + rng.gen_range(1..4))];
+            let mut found = sa.occurrences(pattern).to_vec();
+            found.sort_unstable();
+            let naive: Vec<usize> = (0..len)
+                .filter(|i| text[*i..].starts_with(pattern))
+                .collect();
+            assert_eq!(found, naive);
+
+            let longest = (1..len).rev().find(|l| {
+                (0..=len - l).any(|i| (i + 1..=len - l).any(|j| text[i..i + l] == text[j..j + l]))
+            });
+            assert_eq!(sa.longest_repeated_substring().map(|s| s.len()), longest);
+        }
+    }
+}